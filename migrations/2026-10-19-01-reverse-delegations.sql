@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS reverse_delegations (
+  id uuid PRIMARY KEY DEFAULT uuid_generate_v4(),
+  prefix varchar(64) NOT NULL UNIQUE,
+  owner_uuid uuid NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+  constraint owner_uuid_fk foreign key (owner_uuid) references users (id) ON DELETE CASCADE
+);
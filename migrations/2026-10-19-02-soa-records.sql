@@ -0,0 +1,7 @@
+-- Zones created before SOA management have no SOA record, give them the defaults
+INSERT INTO records(zone_id,name,type,content,ttl)
+  SELECT id, id, 'SOA',
+    'ns1.hostsdottxt.net. hostmaster.' || id || ' ' || to_char(now() AT TIME ZONE 'UTC', 'YYYYMMDD') || '00 7200 3600 1209600 3600',
+    3600
+  FROM zones
+  WHERE NOT EXISTS (SELECT 1 FROM records WHERE records.zone_id = zones.id AND records.type = 'SOA');
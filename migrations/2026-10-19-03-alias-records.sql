@@ -0,0 +1,4 @@
+-- A/AAAA records synthesized from an ALIAS record point back at it
+ALTER TABLE records ADD COLUMN IF NOT EXISTS alias_id uuid;
+ALTER TABLE records DROP CONSTRAINT IF EXISTS alias_id_fk;
+ALTER TABLE records ADD constraint alias_id_fk foreign key (alias_id) references records (id) ON DELETE CASCADE;
@@ -0,0 +1 @@
+ALTER TABLE zones ADD COLUMN IF NOT EXISTS default_ttl INTEGER NOT NULL DEFAULT 3600;
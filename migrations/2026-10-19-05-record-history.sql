@@ -0,0 +1,17 @@
+-- Append-only log of every change made to a record. The values are kept as
+-- JSON so the history outlives the record itself.
+CREATE TABLE IF NOT EXISTS record_history (
+  id BIGSERIAL PRIMARY KEY,
+  zone_id varchar(255) NOT NULL,
+  record_id uuid NOT NULL,
+  action varchar(16) NOT NULL,
+  old_value jsonb,
+  new_value jsonb,
+  actor uuid,
+  client_ip varchar(45),
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+  constraint zone_id_fk foreign key (zone_id) references zones (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS record_history_zone_idx ON record_history (zone_id, created_at);
+CREATE INDEX IF NOT EXISTS record_history_record_idx ON record_history (record_id);
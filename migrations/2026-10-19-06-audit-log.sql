@@ -0,0 +1,14 @@
+-- Security-relevant events for compliance. There's deliberately no foreign key
+-- on user_id so events outlive the user they're about.
+CREATE TABLE IF NOT EXISTS audit_log (
+  id BIGSERIAL PRIMARY KEY,
+  user_id uuid,
+  event varchar(64) NOT NULL,
+  success boolean NOT NULL,
+  client_ip varchar(45),
+  details jsonb,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_user_idx ON audit_log (user_id, created_at);
+CREATE INDEX IF NOT EXISTS audit_log_event_idx ON audit_log (event, created_at);
@@ -0,0 +1,16 @@
+-- Batches of record changes applied at a later time by `tasks::scheduled`.
+-- status is one of pending, applied, failed or cancelled.
+CREATE TABLE IF NOT EXISTS scheduled_changes (
+  id uuid PRIMARY KEY DEFAULT uuid_generate_v4(),
+  zone_id varchar(255) NOT NULL,
+  created_by uuid NOT NULL,
+  apply_at TIMESTAMP WITH TIME ZONE NOT NULL,
+  changes jsonb NOT NULL,
+  status varchar(16) NOT NULL DEFAULT 'pending',
+  error TEXT,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+  applied_at TIMESTAMP WITH TIME ZONE,
+  constraint zone_id_fk foreign key (zone_id) references zones (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS scheduled_changes_due_idx ON scheduled_changes (status, apply_at);
@@ -0,0 +1,4 @@
+-- Records with an expires_at are deleted by `tasks::expiry` once it passes.
+ALTER TABLE records ADD COLUMN IF NOT EXISTS expires_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX IF NOT EXISTS records_expires_at_idx ON records (expires_at) WHERE expires_at IS NOT NULL;
@@ -0,0 +1,3 @@
+-- Free-form notes on records, e.g. which team owns them and why they exist
+ALTER TABLE records ADD COLUMN IF NOT EXISTS comment TEXT;
+ALTER TABLE records ADD COLUMN IF NOT EXISTS tags TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,3 @@
+-- Disabled records are kept, with their history, but must be left out of the
+-- zone by anything that serves it.
+ALTER TABLE records ADD COLUMN IF NOT EXISTS enabled boolean NOT NULL DEFAULT true;
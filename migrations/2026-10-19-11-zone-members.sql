@@ -0,0 +1,17 @@
+-- Users who can access a zone, and what they can do with it. zones.owner_uuid
+-- is kept as the user who created the zone.
+CREATE TABLE IF NOT EXISTS zone_members (
+  zone_id varchar(255) NOT NULL,
+  user_id uuid NOT NULL,
+  role varchar(16) NOT NULL CHECK (role IN ('owner', 'editor', 'viewer')),
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+  PRIMARY KEY (zone_id, user_id),
+  constraint zone_id_fk foreign key (zone_id) references zones (id) ON DELETE CASCADE,
+  constraint user_id_fk foreign key (user_id) references users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS zone_members_user_id_idx ON zone_members (user_id);
+
+INSERT INTO zone_members (zone_id, user_id, role)
+  SELECT id, owner_uuid, 'owner' FROM zones
+  ON CONFLICT DO NOTHING;
@@ -0,0 +1,79 @@
+use crate::db::models::{Actor, AuditEvent};
+use crate::db::strings;
+use chrono::{DateTime, Utc};
+use log::error;
+use serde_json::Value;
+use sqlx::types::Uuid;
+use sqlx::{Pool, Postgres};
+
+/// Narrows down the events returned by [`get_events`]. `None` matches anything.
+pub struct AuditFilter {
+    pub user_id: Option<Uuid>,
+    pub event: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Only return events older than this event id
+    pub before: i64,
+    pub limit: i64,
+}
+
+/// Records an event in the audit log. The write happens in the background so it
+/// doesn't hold up the request that triggered it, and a failure is logged
+/// rather than failing that request.
+///
+/// Events currently recorded are `login`, `signup`, `api_key.use`,
+/// `zone.create`, `member.add`, `member.remove`, `delegation.create` and
+/// `delegation.delete`. API keys can't be created or revoked and zones can't be
+/// deleted through the API yet, so there are no `api_key.create`,
+/// `api_key.revoke` or `zone.delete` events; those endpoints should record them
+/// when they're added.
+pub fn record(
+    pool: &Pool<Postgres>,
+    actor: &Actor,
+    event: &'static str,
+    success: bool,
+    details: Value,
+) {
+    let pool = pool.clone();
+    let actor = *actor;
+    tokio::spawn(async move {
+        if let Err(e) = log_event(&pool, &actor, event, success, &details).await {
+            error!("Failed to record audit event {event}: {e}");
+        }
+    });
+}
+
+pub async fn log_event(
+    pool: &Pool<Postgres>,
+    actor: &Actor,
+    event: &str,
+    success: bool,
+    details: &Value,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(&strings::CREATE_AUDIT_EVENT)
+        .bind(actor.user_id)
+        .bind(event)
+        .bind(success)
+        .bind(actor.client_ip.map(|ip| ip.to_string()))
+        .bind(details)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Returns the events matching `filter`, newest first
+pub async fn get_events(
+    pool: &Pool<Postgres>,
+    filter: &AuditFilter,
+) -> Result<Vec<AuditEvent>, sqlx::Error> {
+    let events = sqlx::query_as::<_, AuditEvent>(&strings::GET_AUDIT_EVENTS)
+        .bind(filter.user_id)
+        .bind(&filter.event)
+        .bind(filter.since)
+        .bind(filter.until)
+        .bind(filter.before)
+        .bind(filter.limit)
+        .fetch_all(pool)
+        .await?;
+    Ok(events)
+}
@@ -0,0 +1,47 @@
+use crate::db::models::Delegation;
+use crate::db::strings;
+use sqlx::types::Uuid;
+use sqlx::{Pool, Postgres};
+
+pub async fn create_delegation(
+    pool: &Pool<Postgres>,
+    prefix: &str,
+    owner_uuid: Uuid,
+) -> Result<Delegation, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let delegation = sqlx::query_as::<_, Delegation>(&strings::CREATE_DELEGATION)
+        .bind(prefix)
+        .bind(owner_uuid)
+        .fetch_one(&mut transaction)
+        .await?;
+    transaction.commit().await?;
+    Ok(delegation)
+}
+
+pub async fn get_all_delegations(pool: &Pool<Postgres>) -> Result<Vec<Delegation>, sqlx::Error> {
+    let delegations = sqlx::query_as::<_, Delegation>(&strings::GET_ALL_DELEGATIONS)
+        .fetch_all(pool)
+        .await?;
+    Ok(delegations)
+}
+
+pub async fn get_delegations(
+    pool: &Pool<Postgres>,
+    owner_uuid: Uuid,
+) -> Result<Vec<Delegation>, sqlx::Error> {
+    let delegations = sqlx::query_as::<_, Delegation>(&strings::GET_DELEGATIONS)
+        .bind(owner_uuid)
+        .fetch_all(pool)
+        .await?;
+    Ok(delegations)
+}
+
+pub async fn delete_delegation(pool: &Pool<Postgres>, id: &Uuid) -> Result<u64, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let result = sqlx::query(&strings::DELETE_DELEGATION)
+        .bind(id)
+        .execute(&mut transaction)
+        .await?;
+    transaction.commit().await?;
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,80 @@
+use crate::db::models::{Actor, Record, RecordHistory};
+use crate::db::strings;
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sqlx::types::Uuid;
+use sqlx::{Executor, Pool, Postgres, Transaction};
+
+/// Appends a history entry for a record. `old` is `None` for a create and `new`
+/// is `None` for a delete.
+pub(crate) async fn log_change(
+    transaction: &mut Transaction<'_, Postgres>,
+    actor: &Actor,
+    old: Option<&Record>,
+    new: Option<&Record>,
+) -> Result<(), sqlx::Error> {
+    let (record, action) = match (old, new) {
+        (None, Some(new)) => (new, "create"),
+        (Some(_), Some(new)) => (new, "update"),
+        (Some(old), None) => (old, "delete"),
+        (None, None) => return Ok(()),
+    };
+    sqlx::query(&strings::CREATE_HISTORY)
+        .bind(&record.zone_id)
+        .bind(record.id)
+        .bind(action)
+        .bind(old.map(|r| json!(r)))
+        .bind(new.map(|r| json!(r)))
+        .bind(actor.user_id)
+        .bind(actor.client_ip.map(|ip| ip.to_string()))
+        .execute(&mut *transaction)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_record_history(
+    pool: &Pool<Postgres>,
+    zone_id: &str,
+    record_id: &Uuid,
+) -> Result<Vec<RecordHistory>, sqlx::Error> {
+    let history = sqlx::query_as::<_, RecordHistory>(&strings::GET_RECORD_HISTORY)
+        .bind(zone_id)
+        .bind(record_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(history)
+}
+
+/// Returns up to `limit` entries for the zone older than the entry `before`,
+/// newest first
+pub async fn get_zone_history(
+    pool: &Pool<Postgres>,
+    zone_id: &str,
+    before: i64,
+    limit: i64,
+) -> Result<Vec<RecordHistory>, sqlx::Error> {
+    let history = sqlx::query_as::<_, RecordHistory>(&strings::GET_ZONE_HISTORY)
+        .bind(zone_id)
+        .bind(before)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+    Ok(history)
+}
+
+/// Returns every entry for the zone made after `since`, newest first
+pub async fn get_zone_history_since<'c, E>(
+    executor: E,
+    zone_id: &str,
+    since: &DateTime<Utc>,
+) -> Result<Vec<RecordHistory>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let history = sqlx::query_as::<_, RecordHistory>(&strings::GET_ZONE_HISTORY_SINCE)
+        .bind(zone_id)
+        .bind(since)
+        .fetch_all(executor)
+        .await?;
+    Ok(history)
+}
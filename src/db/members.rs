@@ -0,0 +1,101 @@
+use crate::db::models::{Role, ZoneMember};
+use crate::db::strings;
+use sqlx::types::Uuid;
+use sqlx::{Pool, Postgres, Transaction};
+
+/// The user's role in the zone, or `None` if they aren't a member
+pub async fn get_role(
+    pool: &Pool<Postgres>,
+    zone_id: &str,
+    user_id: Uuid,
+) -> Result<Option<Role>, sqlx::Error> {
+    let role: Option<String> = sqlx::query_scalar(&strings::GET_MEMBER_ROLE)
+        .bind(zone_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+    role.map(|role| {
+        role.parse()
+            .map_err(|e: String| sqlx::Error::Decode(e.into()))
+    })
+    .transpose()
+}
+
+pub async fn get_members(
+    pool: &Pool<Postgres>,
+    zone_id: &str,
+) -> Result<Vec<ZoneMember>, sqlx::Error> {
+    let members = sqlx::query_as::<_, ZoneMember>(&strings::GET_MEMBERS)
+        .bind(zone_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(members)
+}
+
+/// Adds a member or changes their role. Returns `None`, changing nothing, if
+/// this would leave the zone without an owner.
+pub async fn set_member(
+    pool: &Pool<Postgres>,
+    zone_id: &str,
+    user_id: Uuid,
+    role: Role,
+) -> Result<Option<ZoneMember>, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    lock_zone(&mut transaction, zone_id).await?;
+    let member = sqlx::query_as::<_, ZoneMember>(&strings::SET_MEMBER)
+        .bind(zone_id)
+        .bind(user_id)
+        .bind(role.as_str())
+        .fetch_one(&mut transaction)
+        .await?;
+    if !has_owner(&mut transaction, zone_id).await? {
+        return Ok(None);
+    }
+    transaction.commit().await?;
+    Ok(Some(member))
+}
+
+/// Removes a member, returning `None`, changing nothing, if this would leave the
+/// zone without an owner. Fails with `RowNotFound` if they aren't a member.
+pub async fn remove_member(
+    pool: &Pool<Postgres>,
+    zone_id: &str,
+    user_id: Uuid,
+) -> Result<Option<ZoneMember>, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    lock_zone(&mut transaction, zone_id).await?;
+    let member = sqlx::query_as::<_, ZoneMember>(&strings::DELETE_MEMBER)
+        .bind(zone_id)
+        .bind(user_id)
+        .fetch_one(&mut transaction)
+        .await?;
+    if !has_owner(&mut transaction, zone_id).await? {
+        return Ok(None);
+    }
+    transaction.commit().await?;
+    Ok(Some(member))
+}
+
+/// Serializes membership changes to a zone, so two owners can't demote each
+/// other at the same time
+async fn lock_zone(
+    transaction: &mut Transaction<'_, Postgres>,
+    zone_id: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(&strings::LOCK_ZONE)
+        .bind(zone_id)
+        .fetch_one(&mut *transaction)
+        .await?;
+    Ok(())
+}
+
+async fn has_owner(
+    transaction: &mut Transaction<'_, Postgres>,
+    zone_id: &str,
+) -> Result<bool, sqlx::Error> {
+    let owners: i64 = sqlx::query_scalar(&strings::COUNT_OWNERS)
+        .bind(zone_id)
+        .fetch_one(&mut *transaction)
+        .await?;
+    Ok(owners > 0)
+}
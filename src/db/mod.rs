@@ -1,5 +1,10 @@
+pub mod audit;
+pub mod delegations;
+pub mod history;
+pub mod members;
 pub mod metrics;
 pub mod records;
+pub mod scheduled;
 pub mod users;
 pub mod zones;
 
@@ -1,7 +1,10 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use sqlx::types::Uuid;
 use sqlx::FromRow;
+use std::net::IpAddr;
+use std::str::FromStr;
 
 #[derive(Serialize, Deserialize, FromRow, Debug)]
 pub struct User {
@@ -23,10 +26,11 @@ pub struct Zone {
     pub owner_uuid: Uuid,
     pub created_at: DateTime<Utc>,
     pub modified_at: DateTime<Utc>,
+    pub default_ttl: i32,
     // pub enabled: bool,
 }
 
-#[derive(Serialize, Deserialize, FromRow, Debug)]
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
 pub struct Record {
     pub id: Uuid,
     pub zone_id: String,
@@ -38,6 +42,83 @@ pub struct Record {
     pub ttl: i32,
     pub created_at: DateTime<Utc>,
     pub modified_at: DateTime<Utc>,
+    pub alias_id: Option<Uuid>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub comment: Option<String>,
+    // Missing from history entries written before tags were added
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Disabled records are kept, but left out of the zone
+    #[serde(default = "enabled_default")]
+    pub enabled: bool,
+}
+
+fn enabled_default() -> bool {
+    true
+}
+
+impl Zone {
+    /// Entity tag for optimistic concurrency. The zone's `modified_at` is bumped
+    /// whenever one of its records changes, so this covers the records too.
+    pub fn etag(&self) -> String {
+        etag(&self.modified_at)
+    }
+}
+
+impl Record {
+    /// Entity tag for optimistic concurrency
+    pub fn etag(&self) -> String {
+        etag(&self.modified_at)
+    }
+}
+
+fn etag(modified_at: &DateTime<Utc>) -> String {
+    format!("\"{:x}\"", modified_at.timestamp_micros())
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug)]
+pub struct Delegation {
+    pub id: Uuid,
+    pub prefix: String,
+    pub owner_uuid: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug)]
+pub struct RecordHistory {
+    pub id: i64,
+    pub zone_id: String,
+    pub record_id: Uuid,
+    pub action: String,
+    pub old_value: Option<Value>,
+    pub new_value: Option<Value>,
+    pub actor: Option<Uuid>,
+    pub client_ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug)]
+pub struct AuditEvent {
+    pub id: i64,
+    pub user_id: Option<Uuid>,
+    pub event: String,
+    pub success: bool,
+    pub client_ip: Option<String>,
+    pub details: Option<Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug)]
+pub struct ScheduledChange {
+    pub id: Uuid,
+    pub zone_id: String,
+    pub created_by: Uuid,
+    pub apply_at: DateTime<Utc>,
+    pub changes: Value,
+    pub status: String,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub applied_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Serialize, Deserialize, FromRow, Debug)]
@@ -49,3 +130,56 @@ pub struct Metrics {
     pub avg: f64,
     pub count: i64,
 }
+
+#[derive(Serialize, Deserialize, FromRow, Debug)]
+pub struct ZoneMember {
+    pub zone_id: String,
+    pub user_id: Uuid,
+    pub email: String,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What a member can do with a zone. Each role can do everything the ones
+/// before it can.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Read the zone, its records and their history
+    Viewer,
+    /// Change records
+    Editor,
+    /// Change zone settings and members
+    Owner,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Owner => "owner",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(Role::Viewer),
+            "editor" => Ok(Role::Editor),
+            "owner" => Ok(Role::Owner),
+            _ => Err(format!("Unknown role `{s}`")),
+        }
+    }
+}
+
+/// Who made a change, stored with it in the record history and audit log.
+/// Changes made by HOSTSdotTXT itself have no user.
+#[derive(Clone, Copy, Debug)]
+pub struct Actor {
+    pub user_id: Option<Uuid>,
+    pub client_ip: Option<IpAddr>,
+}
@@ -1,65 +1,278 @@
-use crate::db::models::Record;
-use crate::db::strings;
+use crate::db::history;
+use crate::db::models::{Actor, Record, RecordHistory, Zone};
+use crate::db::{strings, zones};
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgArguments;
+use sqlx::query::Query;
 use sqlx::types::Uuid;
-use sqlx::{Pool, Postgres};
+use sqlx::{FromRow, Pool, Postgres, Row, Transaction};
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+
+/// The fields of a record that are written by users
+#[derive(Debug, Clone)]
+pub struct RecordData {
+    pub name: String,
+    pub record_type: String,
+    pub content: String,
+    pub ttl: i32,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub comment: Option<String>,
+    pub tags: Vec<String>,
+    pub enabled: bool,
+}
+
+impl From<&Record> for RecordData {
+    fn from(record: &Record) -> Self {
+        RecordData {
+            name: record.name.clone(),
+            record_type: record.record_type.clone(),
+            content: record.content.clone(),
+            ttl: record.ttl,
+            expires_at: record.expires_at,
+            comment: record.comment.clone(),
+            tags: record.tags.clone(),
+            enabled: record.enabled,
+        }
+    }
+}
+
+/// A single write in a batch applied by [`write_zone`]
+pub enum Change {
+    Create(RecordData),
+    Update { id: Uuid, data: RecordData },
+    Delete { id: Uuid },
+}
 
 pub async fn create_record(
     pool: &Pool<Postgres>,
+    actor: &Actor,
     zone_id: &str,
-    name: &str,
-    record_type: &str,
-    content: &str,
-    ttl: u32,
+    data: &RecordData,
 ) -> Result<Record, sqlx::Error> {
     let mut transaction = pool.begin().await?;
-    let zone = sqlx::query_as::<_, Record>(&strings::CREATE_RECORD)
+    let record = insert(&mut transaction, actor, zone_id, data).await?;
+    zones::bump_serial(&mut transaction, zone_id).await?;
+    transaction.commit().await?;
+    Ok(record)
+}
+
+/// Deletes a record if it has expired, returning `None` (and writing nothing)
+/// if it's gone or no longer expired, e.g. because its expiry was pushed back
+/// since it was listed
+pub async fn delete_expired_record(
+    pool: &Pool<Postgres>,
+    actor: &Actor,
+    zone_id: &str,
+    record_id: &Uuid,
+) -> Result<Option<Record>, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    sqlx::query(&strings::LOCK_ZONE)
         .bind(zone_id)
-        .bind(name)
-        .bind(record_type)
-        .bind(content)
-        .bind(ttl)
         .fetch_one(&mut transaction)
         .await?;
-    transaction.commit().await?;
-    Ok(zone)
+    let record = sqlx::query_as::<_, Record>(&strings::DELETE_EXPIRED_RECORD)
+        .bind(record_id)
+        .bind(zone_id)
+        .fetch_optional(&mut transaction)
+        .await?;
+    if let Some(record) = &record {
+        history::log_change(&mut transaction, actor, Some(record), None).await?;
+        zones::bump_serial(&mut transaction, zone_id).await?;
+        transaction.commit().await?;
+    }
+    Ok(record)
+}
+
+/// Plans and applies a batch of changes to a zone in a single transaction,
+/// bumping the serial once. The zone row is locked before its records are
+/// loaded, so `plan` sees the zone exactly as the changes will be applied to it
+/// and concurrent writers to the zone wait their turn. Nothing is written if
+/// `plan` fails or returns no changes.
+///
+/// Returns each change alongside the written record, which is `None` for a
+/// delete. If any change fails (including an update or delete of a record that
+/// no longer exists) nothing is applied.
+pub async fn write_zone<E>(
+    pool: &Pool<Postgres>,
+    actor: &Actor,
+    zone_id: &str,
+    plan: impl FnOnce(&Zone, Vec<Record>) -> Result<Vec<Change>, E>,
+) -> Result<Result<Vec<(Change, Option<Record>)>, E>, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let results = write_zone_in(&mut transaction, actor, zone_id, plan).await?;
+    if results.is_ok() {
+        transaction.commit().await?;
+    }
+    Ok(results)
+}
+
+/// Like [`write_zone`], but in a transaction the caller commits
+pub(crate) async fn write_zone_in<E>(
+    transaction: &mut Transaction<'_, Postgres>,
+    actor: &Actor,
+    zone_id: &str,
+    plan: impl FnOnce(&Zone, Vec<Record>) -> Result<Vec<Change>, E>,
+) -> Result<Result<Vec<(Change, Option<Record>)>, E>, sqlx::Error> {
+    let (zone, records) = lock_zone(transaction, zone_id).await?;
+    let changes = match plan(&zone, records) {
+        Ok(changes) => changes,
+        Err(e) => return Ok(Err(e)),
+    };
+    let results = apply(transaction, actor, zone_id, changes).await?;
+    Ok(Ok(results))
 }
 
-pub async fn update_record(
+/// Like [`write_zone`], but also passes `plan` every history entry for the zone
+/// made after `since`, newest first, for rolling the zone back to that time
+pub async fn restore_zone<E>(
     pool: &Pool<Postgres>,
+    actor: &Actor,
+    zone_id: &str,
+    since: &DateTime<Utc>,
+    plan: impl FnOnce(&Zone, Vec<Record>, Vec<RecordHistory>) -> Result<Vec<Change>, E>,
+) -> Result<Result<Vec<(Change, Option<Record>)>, E>, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let (zone, records) = lock_zone(&mut transaction, zone_id).await?;
+    let history = history::get_zone_history_since(&mut transaction, zone_id, since).await?;
+    let changes = match plan(&zone, records, history) {
+        Ok(changes) => changes,
+        Err(e) => return Ok(Err(e)),
+    };
+    let results = apply(&mut transaction, actor, zone_id, changes).await?;
+    transaction.commit().await?;
+    Ok(Ok(results))
+}
+
+async fn lock_zone(
+    transaction: &mut Transaction<'_, Postgres>,
+    zone_id: &str,
+) -> Result<(Zone, Vec<Record>), sqlx::Error> {
+    let zone = sqlx::query_as::<_, Zone>(&strings::LOCK_ZONE)
+        .bind(zone_id)
+        .fetch_one(&mut *transaction)
+        .await?;
+    let records = sqlx::query_as::<_, Record>(&strings::GET_RECORDS)
+        .bind(zone_id)
+        .fetch_all(&mut *transaction)
+        .await?;
+    Ok((zone, records))
+}
+
+async fn apply(
+    transaction: &mut Transaction<'_, Postgres>,
+    actor: &Actor,
+    zone_id: &str,
+    changes: Vec<Change>,
+) -> Result<Vec<(Change, Option<Record>)>, sqlx::Error> {
+    if changes.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut results = Vec::with_capacity(changes.len());
+    for change in changes {
+        let result = match &change {
+            Change::Create(data) => Some(insert(transaction, actor, zone_id, data).await?),
+            Change::Update { id, data } => {
+                Some(update(transaction, actor, zone_id, id, data).await?)
+            }
+            Change::Delete { id } => {
+                if delete(transaction, actor, zone_id, id).await?.is_none() {
+                    return Err(sqlx::Error::RowNotFound);
+                }
+                None
+            }
+        };
+        results.push((change, result));
+    }
+    zones::bump_serial(transaction, zone_id).await?;
+    Ok(results)
+}
+
+pub(crate) async fn insert(
+    transaction: &mut Transaction<'_, Postgres>,
+    actor: &Actor,
+    zone_id: &str,
+    data: &RecordData,
+) -> Result<Record, sqlx::Error> {
+    let record = sqlx::query_as::<_, Record>(&strings::CREATE_RECORD)
+        .bind(zone_id)
+        .bind(&data.name)
+        .bind(&data.record_type)
+        .bind(&data.content)
+        .bind(data.ttl)
+        .bind(data.expires_at)
+        .bind(&data.comment)
+        .bind(&data.tags)
+        .bind(data.enabled)
+        .fetch_one(&mut *transaction)
+        .await?;
+    history::log_change(transaction, actor, None, Some(&record)).await?;
+    Ok(record)
+}
+
+async fn update(
+    transaction: &mut Transaction<'_, Postgres>,
+    actor: &Actor,
     zone_id: &str,
     record_id: &Uuid,
-    name: &str,
-    record_type: &str,
-    content: &str,
-    ttl: u32,
+    data: &RecordData,
 ) -> Result<Record, sqlx::Error> {
-    let mut transaction = pool.begin().await?;
+    let old = sqlx::query_as::<_, Record>(&strings::GET_RECORD_FOR_UPDATE)
+        .bind(record_id)
+        .bind(zone_id)
+        .fetch_one(&mut *transaction)
+        .await?;
     let record = sqlx::query_as::<_, Record>(&strings::UPDATE_RECORD)
-        .bind(name)
-        .bind(record_type)
-        .bind(content)
-        .bind(ttl)
+        .bind(&data.name)
+        .bind(&data.record_type)
+        .bind(&data.content)
+        .bind(data.ttl)
+        .bind(data.expires_at)
+        .bind(&data.comment)
+        .bind(&data.tags)
+        .bind(data.enabled)
         .bind(record_id)
         .bind(zone_id)
-        .fetch_one(&mut transaction)
+        .fetch_one(&mut *transaction)
         .await?;
-    transaction.commit().await?;
+    // Records synthesized from an ALIAS are stale once it's no longer an
+    // enabled ALIAS. They're recreated on the next refresh if it's re-enabled.
+    if record.record_type != "ALIAS" || !record.enabled {
+        sqlx::query(&strings::DELETE_ALIAS_TARGETS)
+            .bind(record.id)
+            .execute(&mut *transaction)
+            .await?;
+    }
+    history::log_change(transaction, actor, Some(&old), Some(&record)).await?;
+    Ok(record)
+}
+
+async fn delete(
+    transaction: &mut Transaction<'_, Postgres>,
+    actor: &Actor,
+    zone_id: &str,
+    record_id: &Uuid,
+) -> Result<Option<Record>, sqlx::Error> {
+    let record = sqlx::query_as::<_, Record>(&strings::DELETE_RECORD)
+        .bind(record_id)
+        .bind(zone_id)
+        .fetch_optional(&mut *transaction)
+        .await?;
+    history::log_change(transaction, actor, record.as_ref(), None).await?;
     Ok(record)
 }
 
-pub async fn delete_record(
+pub async fn get_record(
     pool: &Pool<Postgres>,
     zone_id: &str,
     record_id: &Uuid,
-) -> Result<(), sqlx::Error> {
-    let mut transaction = pool.begin().await?;
-    sqlx::query(&strings::DELETE_RECORD)
+) -> Result<Record, sqlx::Error> {
+    let record = sqlx::query_as::<_, Record>(&strings::GET_RECORD)
         .bind(record_id)
         .bind(zone_id)
-        .execute(&mut transaction)
+        .fetch_one(pool)
         .await?;
-    transaction.commit().await?;
-    Ok(())
+    Ok(record)
 }
 
 pub async fn get_records(pool: &Pool<Postgres>, zone_id: &str) -> Result<Vec<Record>, sqlx::Error> {
@@ -69,3 +282,201 @@ pub async fn get_records(pool: &Pool<Postgres>, zone_id: &str) -> Result<Vec<Rec
         .await?;
     Ok(records)
 }
+
+/// Narrows down and orders the records returned by [`list_records`]. `None`
+/// matches anything.
+pub struct RecordFilter {
+    pub record_type: Option<String>,
+    pub name: Option<String>,
+    pub name_prefix: Option<String>,
+    pub name_contains: Option<String>,
+    /// Case-insensitive substring of the content
+    pub content: Option<String>,
+    /// Tags a record must all have
+    pub tags: Vec<String>,
+    /// Case-insensitive substring of the comment
+    pub comment: Option<String>,
+    pub enabled: Option<bool>,
+    /// `name`, `type` or `modified_at`
+    pub sort: String,
+    pub descending: bool,
+    /// Only return records after this sort key and id
+    pub after: Option<(String, Uuid)>,
+    /// Return at most this many records, or all of them if `None`
+    pub limit: Option<usize>,
+}
+
+/// Counts the records matching `filter`, ignoring its paging
+pub async fn count_records(
+    pool: &Pool<Postgres>,
+    zone_id: &str,
+    filter: &RecordFilter,
+) -> Result<i64, sqlx::Error> {
+    let count = bind_filter(
+        sqlx::query(&strings::COUNT_MATCHING_RECORDS),
+        zone_id,
+        filter,
+    )
+    .fetch_one(pool)
+    .await?
+    .try_get(0)?;
+    Ok(count)
+}
+
+/// Returns a page of the records matching `filter`, each with the key it's
+/// sorted on, and whether there are more records after the page
+pub async fn list_records(
+    pool: &Pool<Postgres>,
+    zone_id: &str,
+    filter: &RecordFilter,
+) -> Result<(Vec<(String, Record)>, bool), sqlx::Error> {
+    let query: &str = match filter.descending {
+        true => &strings::LIST_MATCHING_RECORDS_DESC,
+        false => &strings::LIST_MATCHING_RECORDS,
+    };
+    // One extra row tells us whether there's another page
+    let rows = bind_filter(sqlx::query(query), zone_id, filter)
+        .bind(filter.after.as_ref().map(|(key, _)| key))
+        .bind(filter.after.as_ref().map(|(_, id)| id))
+        .bind(filter.limit.map(|limit| limit as i64 + 1))
+        .fetch_all(pool)
+        .await?;
+
+    let mut records = Vec::with_capacity(rows.len());
+    for row in &rows {
+        records.push((row.try_get("sort_key")?, Record::from_row(row)?));
+    }
+    let more = match filter.limit {
+        Some(limit) if records.len() > limit => {
+            records.truncate(limit);
+            true
+        }
+        _ => false,
+    };
+    Ok((records, more))
+}
+
+fn bind_filter<'q>(
+    query: Query<'q, Postgres, PgArguments>,
+    zone_id: &'q str,
+    filter: &'q RecordFilter,
+) -> Query<'q, Postgres, PgArguments> {
+    query
+        .bind(zone_id)
+        .bind(&filter.sort)
+        .bind(&filter.record_type)
+        .bind(&filter.name)
+        .bind(&filter.name_prefix)
+        .bind(&filter.name_contains)
+        .bind(&filter.content)
+        .bind(&filter.tags)
+        .bind(&filter.comment)
+        .bind(filter.enabled)
+}
+
+/// Records whose `expires_at` has passed
+pub async fn get_expired_records(pool: &Pool<Postgres>) -> Result<Vec<Record>, sqlx::Error> {
+    let records = sqlx::query_as::<_, Record>(&strings::GET_EXPIRED_RECORDS)
+        .fetch_all(pool)
+        .await?;
+    Ok(records)
+}
+
+pub async fn get_alias_records(pool: &Pool<Postgres>) -> Result<Vec<Record>, sqlx::Error> {
+    let records = sqlx::query_as::<_, Record>(&strings::GET_ALIAS_RECORDS)
+        .fetch_all(pool)
+        .await?;
+    Ok(records)
+}
+
+/// Replaces the A/AAAA records synthesized from an ALIAS with records for
+/// `addrs`, returning whether anything changed. Nothing is written (and the
+/// serial isn't bumped) if the addresses are the same as last time, or if the
+/// ALIAS was changed, disabled or deleted while `addrs` was being resolved.
+pub async fn replace_alias_targets(
+    pool: &Pool<Postgres>,
+    alias: &Record,
+    addrs: &[IpAddr],
+) -> Result<bool, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let zone = sqlx::query(&strings::LOCK_ZONE)
+        .bind(&alias.zone_id)
+        .fetch_optional(&mut transaction)
+        .await?;
+    if zone.is_none() {
+        return Ok(false);
+    }
+    let locked = sqlx::query_as::<_, Record>(&strings::GET_RECORD)
+        .bind(alias.id)
+        .bind(&alias.zone_id)
+        .fetch_optional(&mut transaction)
+        .await?;
+    let still_current = match locked {
+        Some(locked) => {
+            locked.record_type == "ALIAS"
+                && locked.enabled
+                && locked.name == alias.name
+                && locked.ttl == alias.ttl
+                && locked.content == alias.content
+        }
+        None => false,
+    };
+    if !still_current {
+        return Ok(false);
+    }
+    let current = sqlx::query_as::<_, Record>(&strings::GET_ALIAS_TARGETS)
+        .bind(alias.id)
+        .fetch_all(&mut transaction)
+        .await?;
+    let wanted = match alias_targets(alias, &current, addrs) {
+        Some(wanted) => wanted,
+        None => return Ok(false),
+    };
+
+    sqlx::query(&strings::DELETE_ALIAS_TARGETS)
+        .bind(alias.id)
+        .execute(&mut transaction)
+        .await?;
+    for (record_type, content) in wanted {
+        sqlx::query(&strings::CREATE_ALIAS_TARGET)
+            .bind(&alias.zone_id)
+            .bind(&alias.name)
+            .bind(record_type)
+            .bind(content)
+            .bind(alias.ttl)
+            .bind(alias.id)
+            .execute(&mut transaction)
+            .await?;
+    }
+    zones::bump_serial(&mut transaction, &alias.zone_id).await?;
+    transaction.commit().await?;
+    Ok(true)
+}
+
+/// The `(type, content)` of the records an ALIAS should be flattened into given
+/// the addresses its target resolved to, or `None` if `current` already matches
+pub fn alias_targets(
+    alias: &Record,
+    current: &[Record],
+    addrs: &[IpAddr],
+) -> Option<BTreeSet<(&'static str, String)>> {
+    let wanted: BTreeSet<(&str, String)> = addrs
+        .iter()
+        .map(|addr| match addr {
+            IpAddr::V4(_) => ("A", addr.to_string()),
+            IpAddr::V6(_) => ("AAAA", addr.to_string()),
+        })
+        .collect();
+    let unchanged = current
+        .iter()
+        .all(|r| r.ttl == alias.ttl && r.name == alias.name)
+        && current
+            .iter()
+            .map(|r| (r.record_type.as_str(), r.content.clone()))
+            .collect::<BTreeSet<_>>()
+            == wanted;
+    match unchanged {
+        true => None,
+        false => Some(wanted),
+    }
+}
@@ -0,0 +1,91 @@
+use crate::db::models::ScheduledChange;
+use crate::db::strings;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::types::Uuid;
+use sqlx::{Pool, Postgres, Transaction};
+
+pub async fn create_scheduled_change(
+    pool: &Pool<Postgres>,
+    zone_id: &str,
+    created_by: Uuid,
+    apply_at: &DateTime<Utc>,
+    changes: &Value,
+) -> Result<ScheduledChange, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let scheduled = sqlx::query_as::<_, ScheduledChange>(&strings::CREATE_SCHEDULED_CHANGE)
+        .bind(zone_id)
+        .bind(created_by)
+        .bind(apply_at)
+        .bind(changes)
+        .fetch_one(&mut transaction)
+        .await?;
+    transaction.commit().await?;
+    Ok(scheduled)
+}
+
+pub async fn get_scheduled_changes(
+    pool: &Pool<Postgres>,
+    zone_id: &str,
+) -> Result<Vec<ScheduledChange>, sqlx::Error> {
+    let scheduled = sqlx::query_as::<_, ScheduledChange>(&strings::GET_SCHEDULED_CHANGES)
+        .bind(zone_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(scheduled)
+}
+
+pub async fn get_scheduled_change(
+    pool: &Pool<Postgres>,
+    zone_id: &str,
+    id: &Uuid,
+) -> Result<ScheduledChange, sqlx::Error> {
+    let scheduled = sqlx::query_as::<_, ScheduledChange>(&strings::GET_SCHEDULED_CHANGE)
+        .bind(id)
+        .bind(zone_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(scheduled)
+}
+
+/// Cancels a pending change, returning `None` if it's no longer pending
+pub async fn cancel_scheduled_change(
+    pool: &Pool<Postgres>,
+    zone_id: &str,
+    id: &Uuid,
+) -> Result<Option<ScheduledChange>, sqlx::Error> {
+    let scheduled = sqlx::query_as::<_, ScheduledChange>(&strings::CANCEL_SCHEDULED_CHANGE)
+        .bind(id)
+        .bind(zone_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(scheduled)
+}
+
+/// Claims the oldest pending change whose time has come, locking it until the
+/// transaction ends. Changes claimed by other transactions are skipped.
+pub(crate) async fn claim_due_scheduled_change(
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Option<ScheduledChange>, sqlx::Error> {
+    let scheduled = sqlx::query_as::<_, ScheduledChange>(&strings::CLAIM_DUE_SCHEDULED_CHANGE)
+        .fetch_optional(&mut *transaction)
+        .await?;
+    Ok(scheduled)
+}
+
+pub(crate) async fn finish_scheduled_change(
+    transaction: &mut Transaction<'_, Postgres>,
+    id: &Uuid,
+    error: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(&strings::FINISH_SCHEDULED_CHANGE)
+        .bind(id)
+        .bind(match error {
+            Some(_) => "failed",
+            None => "applied",
+        })
+        .bind(error)
+        .execute(&mut *transaction)
+        .await?;
+    Ok(())
+}
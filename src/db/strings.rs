@@ -1,5 +1,32 @@
 use lazy_static::lazy_static;
 
+/// The records of zone $1 matching the listing filters $3 to $10, along with
+/// the key $2 says they're sorted on. Managed records are left out, see
+/// `rules::is_managed`.
+const MATCHING_RECORDS: &str = r#"
+    SELECT id,zone_id,name,type,content,ttl,created_at,modified_at,alias_id,expires_at,
+        comment,tags,enabled,
+        CASE $2::varchar
+            WHEN 'type' THEN type
+            WHEN 'modified_at' THEN
+                to_char(modified_at AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS.US')
+            ELSE lower(name)
+        END AS sort_key
+        FROM records
+        WHERE zone_id = $1
+            AND alias_id IS NULL
+            AND type <> 'SOA'
+            AND NOT (type = 'NS' AND lower(name) = lower($1))
+            AND ($3::varchar IS NULL OR lower(type) = lower($3))
+            AND ($4::varchar IS NULL OR lower(name) = lower($4))
+            AND ($5::varchar IS NULL OR left(name, length($5)) = $5)
+            AND ($6::varchar IS NULL OR strpos(name, $6) > 0)
+            AND ($7::text IS NULL OR strpos(lower(content), lower($7)) > 0)
+            AND tags @> $8::text[]
+            AND ($9::text IS NULL OR strpos(lower(comment), lower($9)) > 0)
+            AND ($10::boolean IS NULL OR enabled = $10)
+"#;
+
 lazy_static! {
     pub(crate) static ref GET_USER: &'static str = r"
         SELECT id,email,password,created_at,modified_at,admin,enabled,totp_secret
@@ -17,25 +44,132 @@ lazy_static! {
         INSERT INTO zones(id,owner_uuid) VALUES ($1, $2) RETURNING *
     ";
     pub(crate) static ref GET_ZONES: &'static str = r"
-        SELECT id,owner_uuid,created_at,modified_at
-            FROM zones WHERE owner_uuid = $1
+        SELECT id,owner_uuid,zones.created_at,modified_at,default_ttl
+            FROM zones JOIN zone_members ON zone_members.zone_id = zones.id
+            WHERE zone_members.user_id = $1
     ";
     pub(crate) static ref GET_ZONE: &'static str = r"
-        SELECT id,owner_uuid,created_at,modified_at
+        SELECT id,owner_uuid,created_at,modified_at,default_ttl
             FROM zones WHERE id = $1
     ";
+    pub(crate) static ref LOCK_ZONE: &'static str = r"
+        SELECT id,owner_uuid,created_at,modified_at,default_ttl
+            FROM zones WHERE id = $1 FOR UPDATE
+    ";
+    pub(crate) static ref UPDATE_ZONE: &'static str = r"
+        UPDATE zones SET default_ttl = $1 WHERE id = $2 RETURNING *
+    ";
+    pub(crate) static ref TOUCH_ZONE: &'static str = r"
+        UPDATE zones SET modified_at = now() AT TIME ZONE 'UTC' WHERE id = $1
+    ";
     pub(crate) static ref CREATE_RECORD: &'static str = r"
-        INSERT INTO records(zone_id,name,type,content,ttl) VALUES ($1, $2, $3, $4, $5) RETURNING *
+        INSERT INTO records(zone_id,name,type,content,ttl,expires_at,comment,tags,enabled)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING *
     ";
     pub(crate) static ref UPDATE_RECORD: &'static str = r"
-        UPDATE records SET name = $1, type = $2, content = $3, ttl = $4 WHERE id = $5 AND zone_id = $6 RETURNING *
+        UPDATE records SET name = $1, type = $2, content = $3, ttl = $4, expires_at = $5,
+            comment = $6, tags = $7, enabled = $8
+            WHERE id = $9 AND zone_id = $10 RETURNING *
     ";
     pub(crate) static ref DELETE_RECORD: &'static str = r"
-        DELETE FROM records WHERE id = $1 AND zone_id = $2
+        DELETE FROM records WHERE id = $1 AND zone_id = $2 RETURNING *
+    ";
+    pub(crate) static ref DELETE_EXPIRED_RECORD: &'static str = r"
+        DELETE FROM records
+            WHERE id = $1 AND zone_id = $2 AND expires_at <= (now() AT TIME ZONE 'UTC')
+            RETURNING *
+    ";
+    pub(crate) static ref GET_SOA: &'static str = r"
+        SELECT id,zone_id,name,type,content,ttl,created_at,modified_at,alias_id,expires_at,
+            comment,tags,enabled
+            FROM records WHERE zone_id = $1 AND type = 'SOA'
+            FOR UPDATE
+    ";
+    pub(crate) static ref UPDATE_SOA: &'static str = r"
+        UPDATE records SET content = $1 WHERE id = $2 RETURNING *
+    ";
+    pub(crate) static ref GET_RECORD: &'static str = r"
+        SELECT id,zone_id,name,type,content,ttl,created_at,modified_at,alias_id,expires_at,
+            comment,tags,enabled
+            FROM records WHERE id = $1 AND zone_id = $2
+    ";
+    pub(crate) static ref GET_RECORD_FOR_UPDATE: &'static str = r"
+        SELECT id,zone_id,name,type,content,ttl,created_at,modified_at,alias_id,expires_at,
+            comment,tags,enabled
+            FROM records WHERE id = $1 AND zone_id = $2
+            FOR UPDATE
     ";
     pub(crate) static ref GET_RECORDS: &'static str = r"
-        SELECT id,zone_id,name,type,content,ttl,created_at,modified_at
+        SELECT id,zone_id,name,type,content,ttl,created_at,modified_at,alias_id,expires_at,
+            comment,tags,enabled
             FROM records WHERE zone_id = $1
+            ORDER BY name, type, id
+    ";
+    pub(crate) static ref COUNT_MATCHING_RECORDS: String =
+        format!("SELECT count(*) FROM ({MATCHING_RECORDS}) AS matching");
+    pub(crate) static ref LIST_MATCHING_RECORDS: String = format!(
+        r"
+        SELECT * FROM ({MATCHING_RECORDS}) AS matching
+            WHERE ($11::varchar IS NULL OR (sort_key, id) > ($11, $12::uuid))
+            ORDER BY sort_key, id LIMIT $13
+        "
+    );
+    pub(crate) static ref LIST_MATCHING_RECORDS_DESC: String = format!(
+        r"
+        SELECT * FROM ({MATCHING_RECORDS}) AS matching
+            WHERE ($11::varchar IS NULL OR (sort_key, id) < ($11, $12::uuid))
+            ORDER BY sort_key DESC, id DESC LIMIT $13
+        "
+    );
+    pub(crate) static ref GET_EXPIRED_RECORDS: &'static str = r"
+        SELECT id,zone_id,name,type,content,ttl,created_at,modified_at,alias_id,expires_at,
+            comment,tags,enabled
+            FROM records WHERE expires_at <= (now() AT TIME ZONE 'UTC')
+    ";
+    pub(crate) static ref GET_ALIAS_RECORDS: &'static str = r"
+        SELECT id,zone_id,name,type,content,ttl,created_at,modified_at,alias_id,expires_at,
+            comment,tags,enabled
+            FROM records WHERE type = 'ALIAS' AND enabled
+    ";
+    pub(crate) static ref GET_ALIAS_TARGETS: &'static str = r"
+        SELECT id,zone_id,name,type,content,ttl,created_at,modified_at,alias_id,expires_at,
+            comment,tags,enabled
+            FROM records WHERE alias_id = $1
+            FOR UPDATE
+    ";
+    pub(crate) static ref CREATE_ALIAS_TARGET: &'static str = r"
+        INSERT INTO records(zone_id,name,type,content,ttl,alias_id) VALUES ($1, $2, $3, $4, $5, $6)
+    ";
+    pub(crate) static ref DELETE_ALIAS_TARGETS: &'static str = r"
+        DELETE FROM records WHERE alias_id = $1
+    ";
+    pub(crate) static ref GET_MEMBER_ROLE: &'static str = r"
+        SELECT role FROM zone_members WHERE zone_id = $1 AND user_id = $2
+    ";
+    pub(crate) static ref GET_MEMBERS: &'static str = r"
+        SELECT zone_id,user_id,users.email,role,zone_members.created_at
+            FROM zone_members JOIN users ON users.id = zone_members.user_id
+            WHERE zone_id = $1
+            ORDER BY zone_members.created_at, user_id
+    ";
+    pub(crate) static ref SET_MEMBER: &'static str = r"
+        WITH member AS (
+            INSERT INTO zone_members(zone_id,user_id,role) VALUES ($1, $2, $3)
+                ON CONFLICT (zone_id, user_id) DO UPDATE SET role = $3
+                RETURNING *
+        )
+        SELECT zone_id,user_id,users.email,role,member.created_at
+            FROM member JOIN users ON users.id = member.user_id
+    ";
+    pub(crate) static ref DELETE_MEMBER: &'static str = r"
+        WITH member AS (
+            DELETE FROM zone_members WHERE zone_id = $1 AND user_id = $2 RETURNING *
+        )
+        SELECT zone_id,user_id,users.email,role,member.created_at
+            FROM member JOIN users ON users.id = member.user_id
+    ";
+    pub(crate) static ref COUNT_OWNERS: &'static str = r"
+        SELECT count(*) FROM zone_members WHERE zone_id = $1 AND role = 'owner'
     ";
     pub(crate) static ref GET_USER_FROM_API_KEY: &'static str = r"
         SELECT users.id,email,password,users.created_at,modified_at,admin,enabled,totp_secret FROM api_keys
@@ -44,6 +178,81 @@ lazy_static! {
             WHERE api_keys.token_hash = $1 
                 AND api_keys.expires_at > (now() AT TIME ZONE 'UTC');
     ";
+    pub(crate) static ref CREATE_DELEGATION: &'static str = r"
+        INSERT INTO reverse_delegations(prefix,owner_uuid) VALUES ($1, $2) RETURNING *
+    ";
+    pub(crate) static ref GET_ALL_DELEGATIONS: &'static str = r"
+        SELECT id,prefix,owner_uuid,created_at
+            FROM reverse_delegations
+    ";
+    pub(crate) static ref GET_DELEGATIONS: &'static str = r"
+        SELECT id,prefix,owner_uuid,created_at
+            FROM reverse_delegations WHERE owner_uuid = $1
+    ";
+    pub(crate) static ref DELETE_DELEGATION: &'static str = r"
+        DELETE FROM reverse_delegations WHERE id = $1
+    ";
+    pub(crate) static ref CREATE_HISTORY: &'static str = r"
+        INSERT INTO record_history(zone_id,record_id,action,old_value,new_value,actor,client_ip)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+    ";
+    pub(crate) static ref GET_RECORD_HISTORY: &'static str = r"
+        SELECT id,zone_id,record_id,action,old_value,new_value,actor,client_ip,created_at
+            FROM record_history WHERE zone_id = $1 AND record_id = $2
+            ORDER BY id DESC
+    ";
+    pub(crate) static ref GET_ZONE_HISTORY: &'static str = r"
+        SELECT id,zone_id,record_id,action,old_value,new_value,actor,client_ip,created_at
+            FROM record_history WHERE zone_id = $1 AND id < $2
+            ORDER BY id DESC LIMIT $3
+    ";
+    pub(crate) static ref GET_ZONE_HISTORY_SINCE: &'static str = r"
+        SELECT id,zone_id,record_id,action,old_value,new_value,actor,client_ip,created_at
+            FROM record_history WHERE zone_id = $1 AND created_at > $2
+            ORDER BY id DESC
+    ";
+    pub(crate) static ref CREATE_AUDIT_EVENT: &'static str = r"
+        INSERT INTO audit_log(user_id,event,success,client_ip,details) VALUES ($1, $2, $3, $4, $5)
+    ";
+    pub(crate) static ref GET_AUDIT_EVENTS: &'static str = r"
+        SELECT id,user_id,event,success,client_ip,details,created_at
+            FROM audit_log
+            WHERE ($1::uuid IS NULL OR user_id = $1)
+                AND ($2::varchar IS NULL OR event = $2)
+                AND ($3::timestamptz IS NULL OR created_at >= $3)
+                AND ($4::timestamptz IS NULL OR created_at < $4)
+                AND id < $5
+            ORDER BY id DESC LIMIT $6
+    ";
+    pub(crate) static ref CREATE_SCHEDULED_CHANGE: &'static str = r"
+        INSERT INTO scheduled_changes(zone_id,created_by,apply_at,changes) VALUES ($1, $2, $3, $4) RETURNING *
+    ";
+    pub(crate) static ref GET_SCHEDULED_CHANGES: &'static str = r"
+        SELECT id,zone_id,created_by,apply_at,changes,status,error,created_at,applied_at
+            FROM scheduled_changes WHERE zone_id = $1
+            ORDER BY apply_at
+    ";
+    pub(crate) static ref GET_SCHEDULED_CHANGE: &'static str = r"
+        SELECT id,zone_id,created_by,apply_at,changes,status,error,created_at,applied_at
+            FROM scheduled_changes WHERE id = $1 AND zone_id = $2
+    ";
+    pub(crate) static ref CANCEL_SCHEDULED_CHANGE: &'static str = r"
+        UPDATE scheduled_changes SET status = 'cancelled'
+            WHERE id = $1 AND zone_id = $2 AND status = 'pending'
+            RETURNING *
+    ";
+    pub(crate) static ref CLAIM_DUE_SCHEDULED_CHANGE: &'static str = r"
+        SELECT id,zone_id,created_by,apply_at,changes,status,error,created_at,applied_at
+            FROM scheduled_changes
+            WHERE status = 'pending' AND apply_at <= (now() AT TIME ZONE 'UTC')
+            ORDER BY apply_at
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+    ";
+    pub(crate) static ref FINISH_SCHEDULED_CHANGE: &'static str = r"
+        UPDATE scheduled_changes SET status = $2, error = $3, applied_at = (now() AT TIME ZONE 'UTC')
+            WHERE id = $1
+    ";
     pub(crate) static ref GET_METRICS: &'static str = r#"
         SELECT
             percentile_cont(0.50) WITHIN GROUP (ORDER BY queries.duration_us) AS p50,
@@ -1,12 +1,16 @@
-use crate::db::models::Zone;
+use crate::db::models::{Actor, Record, Role, Zone};
+use crate::db::records::{self, RecordData};
 use crate::db::strings;
+use crate::dns::soa::{self, Soa};
 use sqlx::types::Uuid;
-use sqlx::{Pool, Postgres};
+use sqlx::{Pool, Postgres, Transaction};
 
 pub async fn create_zone(
     pool: &Pool<Postgres>,
+    actor: &Actor,
     id: &str,
     owner_uuid: Uuid,
+    soa: &Soa,
 ) -> Result<Zone, sqlx::Error> {
     let mut transaction = pool.begin().await?;
     let zone = sqlx::query_as::<_, Zone>(&strings::CREATE_ZONE)
@@ -14,6 +18,23 @@ pub async fn create_zone(
         .bind(owner_uuid)
         .fetch_one(&mut transaction)
         .await?;
+    let soa = RecordData {
+        name: id.to_owned(),
+        record_type: String::from("SOA"),
+        content: soa.to_string(),
+        ttl: 3600,
+        expires_at: None,
+        comment: None,
+        tags: Vec::new(),
+        enabled: true,
+    };
+    records::insert(&mut transaction, actor, id, &soa).await?;
+    sqlx::query(&strings::SET_MEMBER)
+        .bind(id)
+        .bind(owner_uuid)
+        .bind(Role::Owner.as_str())
+        .execute(&mut transaction)
+        .await?;
     transaction.commit().await?;
     Ok(zone)
 }
@@ -33,3 +54,111 @@ pub async fn get_zone(pool: &Pool<Postgres>, id: &str) -> Result<Zone, sqlx::Err
         .await?;
     Ok(zone)
 }
+
+/// Updates the zone's settings to the default TTL `plan` picks for the zone as
+/// it is now. The zone row is locked first so `plan` can check it hasn't been
+/// modified. Nothing is written if `plan` fails.
+pub async fn update_zone<E>(
+    pool: &Pool<Postgres>,
+    id: &str,
+    plan: impl FnOnce(&Zone) -> Result<i32, E>,
+) -> Result<Result<Zone, E>, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let zone = sqlx::query_as::<_, Zone>(&strings::LOCK_ZONE)
+        .bind(id)
+        .fetch_one(&mut transaction)
+        .await?;
+    let default_ttl = match plan(&zone) {
+        Ok(default_ttl) => default_ttl,
+        Err(e) => return Ok(Err(e)),
+    };
+    let zone = sqlx::query_as::<_, Zone>(&strings::UPDATE_ZONE)
+        .bind(default_ttl)
+        .bind(id)
+        .fetch_one(&mut transaction)
+        .await?;
+    transaction.commit().await?;
+    Ok(Ok(zone))
+}
+
+pub async fn get_soa(pool: &Pool<Postgres>, zone_id: &str) -> Result<Soa, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let record = sqlx::query_as::<_, Record>(&strings::GET_SOA)
+        .bind(zone_id)
+        .fetch_one(&mut transaction)
+        .await?;
+    transaction.commit().await?;
+    parse_soa(&record)
+}
+
+/// Replaces the editable SOA fields with the ones `plan` picks for the SOA as
+/// it is now, bumping the serial. The zone row is locked first so concurrent
+/// updates of different fields don't overwrite each other, and the zone's
+/// `modified_at` is bumped with the SOA. Nothing is written if `plan` fails.
+pub async fn update_soa<E>(
+    pool: &Pool<Postgres>,
+    zone_id: &str,
+    plan: impl FnOnce(&Soa) -> Result<Soa, E>,
+) -> Result<Result<Soa, E>, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    sqlx::query(&strings::LOCK_ZONE)
+        .bind(zone_id)
+        .fetch_one(&mut transaction)
+        .await?;
+    let record = sqlx::query_as::<_, Record>(&strings::GET_SOA)
+        .bind(zone_id)
+        .fetch_one(&mut transaction)
+        .await?;
+    let current = parse_soa(&record)?;
+    let soa = match plan(&current) {
+        Ok(soa) => Soa {
+            serial: soa::next_serial(current.serial),
+            ..soa
+        },
+        Err(e) => return Ok(Err(e)),
+    };
+    sqlx::query(&strings::UPDATE_SOA)
+        .bind(soa.to_string())
+        .bind(record.id)
+        .execute(&mut transaction)
+        .await?;
+    sqlx::query(&strings::TOUCH_ZONE)
+        .bind(zone_id)
+        .execute(&mut transaction)
+        .await?;
+    transaction.commit().await?;
+    Ok(Ok(soa))
+}
+
+/// Increments the zone's SOA serial. This must run in the same transaction as
+/// the record change so secondaries never see new data under an old serial.
+pub(crate) async fn bump_serial(
+    transaction: &mut Transaction<'_, Postgres>,
+    zone_id: &str,
+) -> Result<(), sqlx::Error> {
+    let record = sqlx::query_as::<_, Record>(&strings::GET_SOA)
+        .bind(zone_id)
+        .fetch_optional(&mut *transaction)
+        .await?;
+    if let Some(record) = record {
+        let mut soa = parse_soa(&record)?;
+        soa.serial = soa::next_serial(soa.serial);
+        sqlx::query(&strings::UPDATE_SOA)
+            .bind(soa.to_string())
+            .bind(record.id)
+            .execute(&mut *transaction)
+            .await?;
+    }
+    sqlx::query(&strings::TOUCH_ZONE)
+        .bind(zone_id)
+        .execute(&mut *transaction)
+        .await?;
+    Ok(())
+}
+
+fn parse_soa(record: &Record) -> Result<Soa, sqlx::Error> {
+    record
+        .content
+        .parse::<Soa>()
+        .map_err(|e| sqlx::Error::Decode(e.into()))
+}
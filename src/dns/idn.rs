@@ -0,0 +1,113 @@
+use serde::Serialize;
+use serde_json::Value;
+
+/// Converts a domain name to its ASCII (A-label) form using IDNA 2008 rules.
+/// Labels that are already ASCII are only lowercased, so names like `_dmarc` or
+/// `*` that aren't valid hostnames pass through untouched.
+pub fn to_ascii(name: &str) -> Result<String, String> {
+    let labels: Result<Vec<String>, String> = name
+        .split('.')
+        .map(|label| match label.is_ascii() {
+            // An A-label has to be what its own U-label encodes to, which rules
+            // out ones like `xn--abc-` that only decode to ASCII
+            true if label.to_ascii_lowercase().starts_with("xn--") => {
+                let label = label.to_ascii_lowercase();
+                match idna::domain_to_unicode(&label) {
+                    (unicode, Ok(())) if encode(&unicode).ok().as_ref() == Some(&label) => {
+                        Ok(label)
+                    }
+                    _ => Err(format!("Invalid punycode label `{label}`")),
+                }
+            }
+            true => Ok(label.to_ascii_lowercase()),
+            false => {
+                encode(label).map_err(|_| format!("Invalid internationalized label `{label}`"))
+            }
+        })
+        .collect();
+    Ok(labels?.join("."))
+}
+
+fn encode(label: &str) -> Result<String, idna::Errors> {
+    idna::Config::default()
+        .use_std3_ascii_rules(true)
+        .use_idna_2008_rules(true)
+        .transitional_processing(false)
+        .check_hyphens(true)
+        .to_ascii(label)
+}
+
+/// Converts a domain name to its Unicode (U-label) form for display, leaving
+/// any label that doesn't decode cleanly as-is
+pub fn to_unicode(name: &str) -> String {
+    name.split('.')
+        .map(|label| match label.starts_with("xn--") {
+            true => match idna::domain_to_unicode(label) {
+                (unicode, Ok(())) => unicode,
+                _ => label.to_owned(),
+            },
+            false => label.to_owned(),
+        })
+        .collect::<Vec<String>>()
+        .join(".")
+}
+
+/// Serializes `value`, adding the Unicode form of its `field` alongside it as
+/// `{field}_unicode`
+pub fn with_unicode<T: Serialize>(value: &T, field: &str) -> Value {
+    let mut value = serde_json::to_value(value).unwrap_or(Value::Null);
+    if let Some(object) = value.as_object_mut() {
+        if let Some(name) = object.get(field).and_then(Value::as_str) {
+            let unicode = Value::String(to_unicode(name));
+            object.insert(format!("{field}_unicode"), unicode);
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_ascii_converts_unicode_labels() {
+        assert_eq!(
+            to_ascii("münchen.example."),
+            Ok(String::from("xn--mnchen-3ya.example."))
+        );
+        assert_eq!(
+            to_ascii("Bücher.Example.COM."),
+            Ok(String::from("xn--bcher-kva.example.com."))
+        );
+        assert_eq!(to_ascii("例え.jp"), Ok(String::from("xn--r8jz45g.jp")));
+    }
+
+    #[test]
+    fn to_ascii_leaves_ascii_labels_alone() {
+        assert_eq!(
+            to_ascii("_dmarc.WWW.example.com."),
+            Ok(String::from("_dmarc.www.example.com."))
+        );
+        assert_eq!(
+            to_ascii("*.example.com."),
+            Ok(String::from("*.example.com."))
+        );
+        assert_eq!(
+            to_ascii("XN--MNCHEN-3YA.example."),
+            Ok(String::from("xn--mnchen-3ya.example."))
+        );
+    }
+
+    #[test]
+    fn to_ascii_rejects_invalid_labels() {
+        assert!(to_ascii("xn--invalid-.example.").is_err());
+        assert!(to_ascii("mü nchen.example.").is_err());
+        assert!(to_ascii("-müller.example.").is_err());
+    }
+
+    #[test]
+    fn to_unicode_decodes_punycode() {
+        assert_eq!(to_unicode("xn--mnchen-3ya.example."), "münchen.example.");
+        assert_eq!(to_unicode("www.example."), "www.example.");
+    }
+}
@@ -0,0 +1,7 @@
+pub mod idn;
+pub mod names;
+pub mod rdata;
+pub mod reverse;
+pub mod rules;
+pub mod soa;
+pub mod zonefile;
@@ -0,0 +1,204 @@
+use crate::dns::idn;
+use trust_dns_proto::rr::Name;
+
+/// Compares two domain names, ignoring case
+pub fn same_name(a: &str, b: &str) -> bool {
+    a.eq_ignore_ascii_case(b)
+}
+
+/// Returns true if `name` is strictly below `parent`
+pub fn is_subdomain(name: &str, parent: &str) -> bool {
+    name.to_ascii_lowercase()
+        .ends_with(&format!(".{}", parent.to_ascii_lowercase()))
+}
+
+/// Returns true if `name` is a wildcard owner name like `*.example.com.`
+///
+/// A wildcard only answers queries for names that don't exist in the zone
+/// (RFC 4592). If `foo.example.com.` has any records, or is an empty
+/// non-terminal because something below it does, `*.example.com.` never answers
+/// for it, not even for types `foo.example.com.` lacks. It also only matches one
+/// or more whole labels, so `*.example.com.` doesn't cover the apex.
+pub fn is_wildcard(name: &str) -> bool {
+    name.starts_with("*.")
+}
+
+/// Checks that `*` only appears as the whole leftmost label of a name. Anything
+/// else, like `foo.*.example.com.` or `*foo.example.com.`, is a literal asterisk
+/// as far as DNS is concerned, which is never what the user meant.
+pub fn check_wildcard(name: &str) -> Result<(), String> {
+    let misplaced = name
+        .split('.')
+        .enumerate()
+        .any(|(i, label)| label.contains('*') && (i != 0 || label != "*"));
+    match misplaced {
+        true => Err(String::from(
+            "Wildcards must be the leftmost label of a name, like *.example.com.",
+        )),
+        false => Ok(()),
+    }
+}
+
+/// Turns a user supplied owner name into the fully qualified, lowercase ASCII
+/// form we store, converting any Unicode labels to punycode. `@` means the zone
+/// apex and names without a trailing dot are taken to be relative to the zone,
+/// so `www` in `example.com.` is `www.example.com.`. The result must be the
+/// apex or a subdomain of the zone.
+pub fn qualify_owner(zone_id: &str, name: &str) -> Result<String, String> {
+    let name = name.trim();
+    let fqdn = match name {
+        "@" => zone_id.to_owned(),
+        name if name.ends_with('.') => name.to_owned(),
+        name => format!("{name}.{zone_id}"),
+    };
+    let fqdn = idn::to_ascii(&fqdn)?;
+
+    let parsed =
+        Name::from_ascii(&fqdn).map_err(|e| format!("Invalid record name `{name}` ({e})"))?;
+    if fqdn.trim_end_matches('.').len() > 253 {
+        return Err(String::from(
+            "Record names must be at most 253 characters long",
+        ));
+    }
+    check_wildcard(&fqdn)?;
+    for label in parsed.iter() {
+        check_label(label)?;
+    }
+
+    if !same_name(&fqdn, zone_id) && !is_subdomain(&fqdn, zone_id) {
+        return Err(format!("Record name {fqdn} is not within zone {zone_id}"));
+    }
+
+    Ok(fqdn)
+}
+
+/// Checks a fully qualified hostname that isn't a record owner, like the SOA
+/// rname, label by label. Unlike owner names it can't be a wildcard.
+pub fn check_hostname(name: &str) -> Result<(), String> {
+    let name = name.strip_suffix('.').unwrap_or(name);
+    if name.len() > 253 {
+        return Err(String::from("Names must be at most 253 characters long"));
+    }
+    for label in name.split('.') {
+        if label.is_empty() || label == "*" {
+            return Err(format!("Invalid name `{name}`"));
+        }
+        check_label(label.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Labels are limited to letters, digits, hyphens and underscores (for names
+/// like `_dmarc`), and can't start or end with a hyphen
+pub(crate) fn check_label(label: &[u8]) -> Result<(), String> {
+    let display = String::from_utf8_lossy(label);
+    if label.len() > 63 {
+        return Err(format!("Label `{display}` is longer than 63 characters"));
+    }
+    if label == b"*" {
+        return Ok(());
+    }
+    if !label
+        .iter()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, b'-' | b'_'))
+    {
+        return Err(format!(
+            "Label `{display}` may only contain letters, digits, hyphens and underscores"
+        ));
+    }
+    if label.starts_with(b"-") || label.ends_with(b"-") {
+        return Err(format!(
+            "Label `{display}` can't start or end with a hyphen"
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZONE: &str = "example.com.";
+
+    #[test]
+    fn qualify_owner_resolves_relative_names() {
+        assert_eq!(qualify_owner(ZONE, "@"), Ok(String::from(ZONE)));
+        assert_eq!(
+            qualify_owner(ZONE, "www"),
+            Ok(String::from("www.example.com."))
+        );
+        assert_eq!(
+            qualify_owner(ZONE, " mail.eu "),
+            Ok(String::from("mail.eu.example.com."))
+        );
+        assert_eq!(
+            qualify_owner(ZONE, "www.example.com."),
+            Ok(String::from("www.example.com."))
+        );
+        assert_eq!(qualify_owner(ZONE, "example.com."), Ok(String::from(ZONE)));
+    }
+
+    #[test]
+    fn qualify_owner_lowercases_and_converts_to_punycode() {
+        assert_eq!(
+            qualify_owner(ZONE, "WWW.Example.COM."),
+            Ok(String::from("www.example.com."))
+        );
+        assert_eq!(
+            qualify_owner(ZONE, "München"),
+            Ok(String::from("xn--mnchen-3ya.example.com."))
+        );
+        assert_eq!(
+            qualify_owner("xn--mnchen-3ya.example.", "Straße"),
+            Ok(String::from("xn--strae-oqa.xn--mnchen-3ya.example."))
+        );
+        assert_eq!(
+            qualify_owner(ZONE, "_dmarc"),
+            Ok(String::from("_dmarc.example.com."))
+        );
+    }
+
+    #[test]
+    fn qualify_owner_rejects_names_outside_the_zone() {
+        assert!(qualify_owner(ZONE, "www.example.org.").is_err());
+        assert!(qualify_owner(ZONE, "com.").is_err());
+        assert!(qualify_owner(ZONE, "badexample.com.").is_err());
+    }
+
+    #[test]
+    fn qualify_owner_rejects_invalid_labels() {
+        assert!(qualify_owner(ZONE, "-www").is_err());
+        assert!(qualify_owner(ZONE, "www-").is_err());
+        assert!(qualify_owner(ZONE, "w!w").is_err());
+        assert!(qualify_owner(ZONE, "a..b").is_err());
+        assert!(qualify_owner(ZONE, &"a".repeat(64)).is_err());
+        let long = vec!["a".repeat(63); 4].join(".");
+        assert!(qualify_owner(ZONE, &long).is_err());
+    }
+
+    #[test]
+    fn wildcards_must_be_the_leftmost_label() {
+        assert_eq!(qualify_owner(ZONE, "*"), Ok(String::from("*.example.com.")));
+        assert_eq!(
+            qualify_owner(ZONE, "*.eu"),
+            Ok(String::from("*.eu.example.com."))
+        );
+        assert!(check_wildcard("*.example.com.").is_ok());
+        assert!(check_wildcard("www.example.com.").is_ok());
+        assert!(check_wildcard("foo.*.example.com.").is_err());
+        assert!(check_wildcard("*foo.example.com.").is_err());
+        assert!(check_wildcard("f*o.example.com.").is_err());
+        assert!(check_wildcard("**.example.com.").is_err());
+        assert!(qualify_owner(ZONE, "foo.*").is_err());
+        assert!(is_wildcard("*.example.com."));
+        assert!(!is_wildcard("example.com."));
+    }
+
+    #[test]
+    fn check_hostname_rejects_wildcards_and_bad_labels() {
+        assert!(check_hostname("hostmaster.example.com.").is_ok());
+        assert!(check_hostname("*.example.com.").is_err());
+        assert!(check_hostname("host..example.com.").is_err());
+        assert!(check_hostname("-host.example.com.").is_err());
+    }
+}
@@ -0,0 +1,545 @@
+use crate::dns::idn;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use trust_dns_proto::rr::rdata::{naptr, sshfp, tlsa};
+use trust_dns_proto::rr::{Name, RecordType};
+
+/// Validates record content for the given type, returning it in canonical
+/// presentation format on success
+pub fn validate_record(rtype: &str, content: &str) -> Result<String, String> {
+    // trust-dns doesn't know about these types, so they're handled by hand. ALIAS
+    // is our own, it's flattened into A/AAAA records by `tasks::alias`
+    match rtype {
+        "URI" => return validate_uri(content),
+        "LOC" => return validate_loc(content),
+        "RP" => return validate_rp(content),
+        "DNAME" => return parse_name(content, "DNAME target"),
+        "ALIAS" => return parse_name(content, "ALIAS target"),
+        _ => {}
+    }
+
+    match RecordType::from_str(rtype) {
+        Ok(rtype) => match rtype {
+            RecordType::A => content
+                .parse::<Ipv4Addr>()
+                .map(|ip| ip.to_string())
+                .map_err(|_| String::from("Invalid IPv4 address")),
+            RecordType::AAAA => content
+                .parse::<Ipv6Addr>()
+                .map(|ip| ip.to_string())
+                .map_err(|_| String::from("Invalid IPv6 address")),
+            RecordType::CNAME => {
+                parse_name(content, "CNAME").map_err(|_| String::from("Invalid CNAME"))
+            }
+            RecordType::MX => {
+                parse_name(content, "MX").map_err(|_| String::from("Invalid MX record"))
+            }
+            RecordType::NS => {
+                parse_name(content, "NS").map_err(|_| String::from("Invalid NS record"))
+            }
+            RecordType::PTR => {
+                parse_name(content, "PTR").map_err(|_| String::from("Invalid PTR record"))
+            }
+            RecordType::TXT => Ok(content.to_owned()),
+            RecordType::TLSA => validate_tlsa(content),
+            RecordType::SSHFP => validate_sshfp(content),
+            RecordType::NAPTR => validate_naptr(content),
+            RecordType::HINFO => validate_hinfo(content),
+            _ => Err(String::from("Unknown record type")),
+        },
+        _ => Err(String::from("Unknown record type")),
+    }
+}
+
+/// Validates TLSA content in presentation format: `usage selector matching_type data`
+fn validate_tlsa(content: &str) -> Result<String, String> {
+    let mut fields = content.split_whitespace();
+    let usage = parse_u8_field(fields.next(), "TLSA certificate usage")?;
+    let selector = parse_u8_field(fields.next(), "TLSA selector")?;
+    let matching = parse_u8_field(fields.next(), "TLSA matching type")?;
+    let data: String = fields.collect();
+
+    if let tlsa::CertUsage::Unassigned(_) | tlsa::CertUsage::Private = tlsa::CertUsage::from(usage)
+    {
+        return Err(String::from(
+            "Invalid TLSA certificate usage (must be between 0 and 3)",
+        ));
+    }
+    if let tlsa::Selector::Unassigned(_) | tlsa::Selector::Private = tlsa::Selector::from(selector)
+    {
+        return Err(String::from(
+            "Invalid TLSA selector (must be between 0 and 1)",
+        ));
+    }
+    let data = parse_hex_field(&data, "TLSA certificate association data")?;
+    match tlsa::Matching::from(matching) {
+        tlsa::Matching::Raw => Ok(()),
+        tlsa::Matching::Sha256 => expect_digest_len(&data, 32, "TLSA SHA-256"),
+        tlsa::Matching::Sha512 => expect_digest_len(&data, 64, "TLSA SHA-512"),
+        _ => Err(String::from(
+            "Invalid TLSA matching type (must be between 0 and 2)",
+        )),
+    }?;
+
+    Ok(format!(
+        "{usage} {selector} {matching} {}",
+        hex::encode(data)
+    ))
+}
+
+/// Validates SSHFP content in presentation format: `algorithm fingerprint_type fingerprint`
+fn validate_sshfp(content: &str) -> Result<String, String> {
+    let mut fields = content.split_whitespace();
+    let algorithm = parse_u8_field(fields.next(), "SSHFP algorithm")?;
+    let fingerprint_type = parse_u8_field(fields.next(), "SSHFP fingerprint type")?;
+    let fingerprint: String = fields.collect();
+
+    if let sshfp::Algorithm::Reserved | sshfp::Algorithm::Unassigned(_) =
+        sshfp::Algorithm::from(algorithm)
+    {
+        return Err(String::from(
+            "Invalid SSHFP algorithm (must be 1, 2, 3, 4 or 6)",
+        ));
+    }
+    let fingerprint = parse_hex_field(&fingerprint, "SSHFP fingerprint")?;
+    match sshfp::FingerprintType::from(fingerprint_type) {
+        sshfp::FingerprintType::SHA1 => expect_digest_len(&fingerprint, 20, "SSHFP SHA-1"),
+        sshfp::FingerprintType::SHA256 => expect_digest_len(&fingerprint, 32, "SSHFP SHA-256"),
+        _ => Err(String::from(
+            "Invalid SSHFP fingerprint type (must be 1 or 2)",
+        )),
+    }?;
+
+    Ok(format!(
+        "{algorithm} {fingerprint_type} {}",
+        hex::encode(fingerprint)
+    ))
+}
+
+/// Validates NAPTR content in presentation format:
+/// `order preference "flags" "services" "regexp" replacement`
+fn validate_naptr(content: &str) -> Result<String, String> {
+    let fields = tokenize(content)?;
+    if fields.len() != 6 {
+        return Err(String::from(
+            "NAPTR records must have the form `order preference \"flags\" \"services\" \"regexp\" replacement`",
+        ));
+    }
+    let order = parse_u16_field(&fields[0], "NAPTR order")?;
+    let preference = parse_u16_field(&fields[1], "NAPTR preference")?;
+    let flags = &fields[2];
+    let services = &fields[3];
+    let regexp = &fields[4];
+    let replacement = parse_name(&fields[5], "NAPTR replacement")?;
+
+    if !naptr::verify_flags(flags.as_bytes()) {
+        return Err(String::from("Invalid NAPTR flags (must be alphanumeric)"));
+    }
+    for (field, what) in [(services, "services"), (regexp, "regexp")] {
+        if field.len() > 255 {
+            return Err(format!("NAPTR {what} must be at most 255 characters"));
+        }
+    }
+    // RFC 3403 4.1: regexp and replacement are mutually exclusive
+    if !regexp.is_empty() && replacement != "." {
+        return Err(String::from(
+            "NAPTR records may not set both a regexp and a replacement",
+        ));
+    }
+
+    Ok(format!(
+        "{order} {preference} {} {} {} {replacement}",
+        quote(&flags.to_ascii_uppercase()),
+        quote(services),
+        quote(regexp)
+    ))
+}
+
+/// Validates URI content in presentation format: `priority weight "target"`
+fn validate_uri(content: &str) -> Result<String, String> {
+    let fields = tokenize(content)?;
+    if fields.len() != 3 {
+        return Err(String::from(
+            "URI records must have the form `priority weight \"target\"`",
+        ));
+    }
+    let priority = parse_u16_field(&fields[0], "URI priority")?;
+    let weight = parse_u16_field(&fields[1], "URI weight")?;
+    let target = &fields[2];
+
+    // RFC 7553 4.5: the target must be a URI, which at the very least has a scheme
+    let valid_scheme = matches!(target.split_once(':'), Some((scheme, _)) if is_uri_scheme(scheme));
+    if !valid_scheme || target.chars().any(char::is_whitespace) {
+        return Err(String::from("Invalid URI target"));
+    }
+
+    Ok(format!("{priority} {weight} {}", quote(target)))
+}
+
+/// Validates HINFO content in presentation format: `"cpu" "os"`
+fn validate_hinfo(content: &str) -> Result<String, String> {
+    let fields = tokenize(content)?;
+    if fields.len() != 2 {
+        return Err(String::from(
+            "HINFO records must have the form `\"cpu\" \"os\"`",
+        ));
+    }
+    if fields.iter().any(|field| field.len() > 255) {
+        return Err(String::from("HINFO fields must be at most 255 characters"));
+    }
+    let (cpu, os) = (&fields[0], &fields[1]);
+
+    Ok(format!("{} {}", quote(cpu), quote(os)))
+}
+
+/// Validates RP content in presentation format: `mbox-dname txt-dname`
+fn validate_rp(content: &str) -> Result<String, String> {
+    let fields: Vec<&str> = content.split_whitespace().collect();
+    if fields.len() != 2 {
+        return Err(String::from(
+            "RP records must have the form `mbox-dname txt-dname`",
+        ));
+    }
+    let mbox = parse_name(fields[0], "RP mailbox")?;
+    let txt = parse_name(fields[1], "RP TXT domain")?;
+
+    Ok(format!("{mbox} {txt}"))
+}
+
+/// Validates LOC content in the RFC 1876 presentation format:
+/// `d1 [m1 [s1]] {N|S} d2 [m2 [s2]] {E|W} alt[m] [siz[m] [hp[m] [vp[m]]]]`
+fn validate_loc(content: &str) -> Result<String, String> {
+    let mut fields = content.split_whitespace().peekable();
+
+    let mut coordinate = |max_degrees: u32, hemispheres: [&str; 2], what: &str| {
+        let mut parts = Vec::new();
+        let hemisphere = loop {
+            match fields.next() {
+                Some(field) if hemispheres.contains(&field.to_ascii_uppercase().as_str()) => {
+                    break field.to_ascii_uppercase()
+                }
+                Some(field) if parts.len() < 3 => parts.push(field),
+                _ => return Err(format!("Invalid LOC {what}")),
+            }
+        };
+        let degrees = parts
+            .first()
+            .and_then(|d| d.parse::<u32>().ok())
+            .filter(|d| *d <= max_degrees)
+            .ok_or_else(|| format!("Invalid LOC {what} degrees"))?;
+        let minutes = match parts.get(1) {
+            Some(m) => m
+                .parse::<u32>()
+                .ok()
+                .filter(|m| *m < 60)
+                .ok_or_else(|| format!("Invalid LOC {what} minutes"))?,
+            None => 0,
+        };
+        let seconds = match parts.get(2) {
+            Some(s) => s
+                .parse::<f64>()
+                .ok()
+                .filter(|s| (0.0..60.0).contains(s))
+                .ok_or_else(|| format!("Invalid LOC {what} seconds"))?,
+            None => 0.0,
+        };
+        if degrees == max_degrees && (minutes > 0 || seconds > 0.0) {
+            return Err(format!("LOC {what} is out of range"));
+        }
+        Ok(format!("{degrees} {minutes} {seconds:.3} {hemisphere}"))
+    };
+
+    let latitude = coordinate(90, ["N", "S"], "latitude")?;
+    let longitude = coordinate(180, ["E", "W"], "longitude")?;
+
+    let mut meters = |what: &str, min: f64, max: f64, default: Option<f64>| {
+        let value = match (fields.next(), default) {
+            (Some(field), _) => field
+                .strip_suffix(['m', 'M'])
+                .unwrap_or(field)
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid LOC {what}"))?,
+            (None, Some(default)) => default,
+            (None, None) => return Err(format!("Missing LOC {what}")),
+        };
+        if !(min..=max).contains(&value) {
+            return Err(format!(
+                "LOC {what} must be between {min:.2}m and {max:.2}m"
+            ));
+        }
+        Ok(format!("{value:.2}m"))
+    };
+
+    let altitude = meters("altitude", -100000.0, 42849672.95, None)?;
+    let size = meters("size", 0.0, 90000000.0, Some(1.0))?;
+    let horizontal_precision = meters("horizontal precision", 0.0, 90000000.0, Some(10000.0))?;
+    let vertical_precision = meters("vertical precision", 0.0, 90000000.0, Some(10.0))?;
+    if fields.peek().is_some() {
+        return Err(String::from("Too many fields in LOC record"));
+    }
+
+    Ok(format!(
+        "{latitude} {longitude} {altitude} {size} {horizontal_precision} {vertical_precision}"
+    ))
+}
+
+/// Parses a domain name, returning it lowercased and fully qualified
+fn parse_name(content: &str, what: &str) -> Result<String, String> {
+    if content.is_empty() || content.chars().any(char::is_whitespace) {
+        return Err(format!("Invalid {what}"));
+    }
+    let ascii = idn::to_ascii(content)?;
+    let mut name = Name::from_ascii(ascii).map_err(|_| format!("Invalid {what}"))?;
+    name.set_fqdn(true);
+    Ok(name.to_lowercase().to_ascii())
+}
+
+/// Splits content into fields, treating double-quoted character-strings as a
+/// single field. Backslash escapes are honoured inside quotes.
+fn tokenize(content: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut chars = content.trim().chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let mut field = String::new();
+        if c == '"' {
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some(escaped) => field.push(escaped),
+                        None => return Err(String::from("Unterminated escape sequence")),
+                    },
+                    Some(c) => field.push(c),
+                    None => return Err(String::from("Unterminated quoted string")),
+                }
+            }
+        } else {
+            field.push(c);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                field.push(c);
+            }
+        }
+        fields.push(field);
+    }
+
+    Ok(fields)
+}
+
+fn is_uri_scheme(scheme: &str) -> bool {
+    scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+fn quote(field: &str) -> String {
+    format!("\"{}\"", field.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn parse_u8_field(field: Option<&str>, what: &str) -> Result<u8, String> {
+    match field {
+        Some(field) => field
+            .parse::<u8>()
+            .map_err(|_| format!("Invalid {what} `{field}`")),
+        None => Err(format!("Missing {what}")),
+    }
+}
+
+fn parse_u16_field(field: &str, what: &str) -> Result<u16, String> {
+    field
+        .parse::<u16>()
+        .map_err(|_| format!("Invalid {what} `{field}`"))
+}
+
+fn parse_hex_field(field: &str, what: &str) -> Result<Vec<u8>, String> {
+    if field.is_empty() {
+        return Err(format!("Missing {what}"));
+    }
+    hex::decode(field).map_err(|_| format!("Invalid {what} (must be hexadecimal)"))
+}
+
+fn expect_digest_len(data: &[u8], len: usize, what: &str) -> Result<(), String> {
+    if data.len() != len {
+        return Err(format!(
+            "Invalid {what} digest (expected {} hex characters, got {})",
+            len * 2,
+            data.len() * 2
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA1: &str = "0123456789abcdef0123456789abcdef01234567";
+    const SHA256: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn tlsa() {
+        assert_eq!(
+            validate_record("TLSA", &format!("3 1 1 {}", SHA256.to_uppercase())),
+            Ok(format!("3 1 1 {SHA256}"))
+        );
+        // The data may be split over several fields
+        assert_eq!(
+            validate_record(
+                "TLSA",
+                &format!("3 1 1 {} {}", &SHA256[..32], &SHA256[32..])
+            ),
+            Ok(format!("3 1 1 {SHA256}"))
+        );
+        assert_eq!(
+            validate_record("TLSA", "3 0 0 30820122"),
+            Ok(String::from("3 0 0 30820122"))
+        );
+        assert!(validate_record("TLSA", &format!("3 1 1 {SHA1}")).is_err());
+        assert!(validate_record("TLSA", &format!("3 1 2 {SHA256}")).is_err());
+        assert!(validate_record("TLSA", &format!("4 1 1 {SHA256}")).is_err());
+        assert!(validate_record("TLSA", &format!("3 2 1 {SHA256}")).is_err());
+        assert!(validate_record("TLSA", &format!("3 1 3 {SHA256}")).is_err());
+        assert!(validate_record("TLSA", "3 1 1 not-hex").is_err());
+        assert!(validate_record("TLSA", "3 1 1").is_err());
+    }
+
+    #[test]
+    fn sshfp() {
+        assert_eq!(
+            validate_record("SSHFP", &format!("4 2 {}", SHA256.to_uppercase())),
+            Ok(format!("4 2 {SHA256}"))
+        );
+        assert_eq!(
+            validate_record("SSHFP", &format!("1 1 {SHA1}")),
+            Ok(format!("1 1 {SHA1}"))
+        );
+        assert!(validate_record("SSHFP", &format!("1 1 {SHA256}")).is_err());
+        assert!(validate_record("SSHFP", &format!("1 2 {SHA1}")).is_err());
+        assert!(validate_record("SSHFP", &format!("5 1 {SHA1}")).is_err());
+        assert!(validate_record("SSHFP", &format!("1 3 {SHA1}")).is_err());
+        assert!(validate_record("SSHFP", "1 1").is_err());
+    }
+
+    #[test]
+    fn naptr() {
+        assert_eq!(
+            validate_record(
+                "NAPTR",
+                r#"100  10 "u" "E2U+sip" "!^.*$!sip:info@example.com!" ."#
+            ),
+            Ok(String::from(
+                r#"100 10 "U" "E2U+sip" "!^.*$!sip:info@example.com!" ."#
+            ))
+        );
+        assert_eq!(
+            validate_record("NAPTR", r#"10 0 "s" "SIP+D2U" "" _sip._udp.Example.com"#),
+            Ok(String::from(
+                r#"10 0 "S" "SIP+D2U" "" _sip._udp.example.com."#
+            ))
+        );
+        assert!(validate_record(
+            "NAPTR",
+            r#"10 0 "u" "E2U+sip" "!^.*$!sip:info@example.com!" example.com."#
+        )
+        .is_err());
+        assert!(validate_record("NAPTR", r#"10 0 "u!" "E2U+sip" "" ."#).is_err());
+        assert!(validate_record("NAPTR", r#"70000 0 "u" "E2U+sip" "" ."#).is_err());
+        assert!(validate_record("NAPTR", r#"10 0 "u" "E2U+sip" """#).is_err());
+        assert!(validate_record("NAPTR", r#"10 0 "u" "E2U+sip "" ."#).is_err());
+    }
+
+    #[test]
+    fn uri() {
+        assert_eq!(
+            validate_record("URI", "10 1 https://example.com/"),
+            Ok(String::from(r#"10 1 "https://example.com/""#))
+        );
+        assert_eq!(
+            validate_record("URI", r#"10 1 "ftp://ftp.example.com/public""#),
+            Ok(String::from(r#"10 1 "ftp://ftp.example.com/public""#))
+        );
+        assert!(validate_record("URI", r#"10 1 "example.com""#).is_err());
+        assert!(validate_record("URI", r#"10 1 "1http://example.com/""#).is_err());
+        assert!(validate_record("URI", r#"10 1 "https://example.com/a b""#).is_err());
+        assert!(validate_record("URI", r#"10 "https://example.com/""#).is_err());
+    }
+
+    #[test]
+    fn loc() {
+        assert_eq!(
+            validate_record(
+                "LOC",
+                "52 22 23.000 N 4 53 32.000 E -2.00m 0.00m 10000m 10m"
+            ),
+            Ok(String::from(
+                "52 22 23.000 N 4 53 32.000 E -2.00m 0.00m 10000.00m 10.00m"
+            ))
+        );
+        assert_eq!(
+            validate_record("LOC", "42 s 73 w 10"),
+            Ok(String::from(
+                "42 0 0.000 S 73 0 0.000 W 10.00m 1.00m 10000.00m 10.00m"
+            ))
+        );
+        assert!(validate_record("LOC", "91 N 0 E 0").is_err());
+        assert!(validate_record("LOC", "90 1 N 0 E 0").is_err());
+        assert!(validate_record("LOC", "0 N 181 E 0").is_err());
+        assert!(validate_record("LOC", "52 60 N 4 E 0").is_err());
+        assert!(validate_record("LOC", "52 N 4 E").is_err());
+        assert!(validate_record("LOC", "52 N 4 E -100001m").is_err());
+        assert!(validate_record("LOC", "52 N 4 E 0 1 1 1 1").is_err());
+    }
+
+    #[test]
+    fn hinfo() {
+        assert_eq!(
+            validate_record("HINFO", "INTEL-386 Linux"),
+            Ok(String::from(r#""INTEL-386" "Linux""#))
+        );
+        assert_eq!(
+            validate_record("HINFO", r#""Intel Core" "Debian GNU/Linux""#),
+            Ok(String::from(r#""Intel Core" "Debian GNU/Linux""#))
+        );
+        assert_eq!(
+            validate_record("HINFO", r#""The \"fast\" one" "C:\\DOS""#),
+            Ok(String::from(r#""The \"fast\" one" "C:\\DOS""#))
+        );
+        assert_eq!(
+            validate_record("HINFO", r#""" """#),
+            Ok(String::from(r#""" """#))
+        );
+        assert!(validate_record("HINFO", r#""Intel Core""#).is_err());
+        assert!(validate_record("HINFO", "a b c").is_err());
+        assert!(validate_record("HINFO", r#""Intel Linux"#).is_err());
+        assert!(validate_record("HINFO", r#""Intel\" Linux"#).is_err());
+        assert!(validate_record("HINFO", &format!("{} Linux", "x".repeat(256))).is_err());
+    }
+
+    #[test]
+    fn rp() {
+        assert_eq!(
+            validate_record("RP", "admin.Example.com. txt.example.com"),
+            Ok(String::from("admin.example.com. txt.example.com."))
+        );
+        assert_eq!(
+            validate_record("RP", "admin.example.com. ."),
+            Ok(String::from("admin.example.com. ."))
+        );
+        assert!(validate_record("RP", "admin.example.com.").is_err());
+        assert!(validate_record("RP", "a.example.com. b.example.com. c.example.com.").is_err());
+    }
+
+    #[test]
+    fn dname() {
+        assert_eq!(
+            validate_record("DNAME", "Example.NET"),
+            Ok(String::from("example.net."))
+        );
+        assert!(validate_record("DNAME", "").is_err());
+        assert!(validate_record("DNAME", "example .net").is_err());
+    }
+}
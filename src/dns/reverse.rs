@@ -0,0 +1,169 @@
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+const IPV4_SUFFIX: &str = "in-addr.arpa.";
+const IPV6_SUFFIX: &str = "ip6.arpa.";
+
+/// Returns the PTR owner name for an address, e.g. `5.113.0.203.in-addr.arpa.`
+pub fn reverse_name(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            format!(
+                "{}.{}.{}.{}.{IPV4_SUFFIX}",
+                octets[3], octets[2], octets[1], octets[0]
+            )
+        }
+        IpAddr::V6(ip) => {
+            let nibbles: String = hex::encode(ip.octets())
+                .chars()
+                .rev()
+                .map(|nibble| format!("{nibble}."))
+                .collect();
+            format!("{nibbles}{IPV6_SUFFIX}")
+        }
+    }
+}
+
+pub fn is_reverse_zone(zone: &str) -> bool {
+    let zone = zone.to_ascii_lowercase();
+    zone.ends_with(&format!(".{IPV4_SUFFIX}")) || zone.ends_with(&format!(".{IPV6_SUFFIX}"))
+}
+
+/// Parses a reverse zone name into the prefix it covers. Only octet-aligned IPv4
+/// zones (/8, /16, /24) and nibble-aligned IPv6 zones are supported, RFC 2317
+/// classless delegations are not.
+pub fn zone_prefix(zone: &str) -> Result<IpNet, String> {
+    let zone = zone.to_ascii_lowercase();
+    if let Some(labels) = zone.strip_suffix(&format!(".{IPV4_SUFFIX}")) {
+        let labels: Vec<&str> = labels.split('.').rev().collect();
+        if labels.len() > 3 {
+            return Err(String::from(
+                "IPv4 reverse zones must cover a /8, /16 or /24",
+            ));
+        }
+        let mut octets = [0u8; 4];
+        for (i, label) in labels.iter().enumerate() {
+            if label.len() > 1 && label.starts_with('0') {
+                return Err(format!("Invalid octet `{label}` in reverse zone"));
+            }
+            octets[i] = label
+                .parse()
+                .map_err(|_| format!("Invalid octet `{label}` in reverse zone"))?;
+        }
+        let net = Ipv4Net::new(Ipv4Addr::from(octets), labels.len() as u8 * 8)
+            .map_err(|e| e.to_string())?;
+        return Ok(IpNet::V4(net));
+    }
+    if let Some(labels) = zone.strip_suffix(&format!(".{IPV6_SUFFIX}")) {
+        let nibbles: String = labels.split('.').rev().collect();
+        if nibbles.len() != labels.split('.').count() || nibbles.len() >= 32 {
+            return Err(String::from(
+                "IPv6 reverse zones must consist of single hex nibbles",
+            ));
+        }
+        let padded = format!("{nibbles:0<32}");
+        let octets: [u8; 16] = hex::decode(&padded)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| format!("Invalid nibble in reverse zone `{zone}`"))?;
+        let net = Ipv6Net::new(Ipv6Addr::from(octets), nibbles.len() as u8 * 4)
+            .map_err(|e| e.to_string())?;
+        return Ok(IpNet::V6(net));
+    }
+    Err(String::from("Not a reverse zone"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn net(prefix: &str) -> IpNet {
+        prefix.parse().unwrap()
+    }
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn reverse_names() {
+        assert_eq!(reverse_name(ip("203.0.113.5")), "5.113.0.203.in-addr.arpa.");
+        assert_eq!(
+            reverse_name(ip("2001:db8::567:89ab")),
+            "b.a.9.8.7.6.5.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa."
+        );
+    }
+
+    #[test]
+    fn octet_aligned_ipv4_zones() {
+        assert_eq!(zone_prefix("10.in-addr.arpa."), Ok(net("10.0.0.0/8")));
+        assert_eq!(
+            zone_prefix("168.192.in-addr.arpa."),
+            Ok(net("192.168.0.0/16"))
+        );
+        assert_eq!(
+            zone_prefix("113.0.203.IN-ADDR.ARPA."),
+            Ok(net("203.0.113.0/24"))
+        );
+    }
+
+    #[test]
+    fn unaligned_ipv4_zones_are_rejected() {
+        // RFC 2317 classless delegations
+        assert!(zone_prefix("0/25.113.0.203.in-addr.arpa.").is_err());
+        assert!(zone_prefix("0-127.113.0.203.in-addr.arpa.").is_err());
+        // A single address isn't a zone
+        assert!(zone_prefix("5.113.0.203.in-addr.arpa.").is_err());
+        assert!(zone_prefix("256.in-addr.arpa.").is_err());
+        assert!(zone_prefix("010.in-addr.arpa.").is_err());
+    }
+
+    #[test]
+    fn nibble_aligned_ipv6_zones() {
+        assert_eq!(
+            zone_prefix("8.b.d.0.1.0.0.2.ip6.arpa."),
+            Ok(net("2001:db8::/32"))
+        );
+        assert_eq!(
+            zone_prefix("0.8.b.d.0.1.0.0.2.ip6.arpa."),
+            Ok(net("2001:db8::/36"))
+        );
+        assert_eq!(zone_prefix("2.ip6.arpa."), Ok(net("2000::/4")));
+    }
+
+    #[test]
+    fn unaligned_ipv6_zones_are_rejected() {
+        assert!(zone_prefix("b8.d0.1.0.0.2.ip6.arpa.").is_err());
+        assert!(zone_prefix("g.b.d.0.1.0.0.2.ip6.arpa.").is_err());
+        let full = reverse_name(ip("2001:db8::1"));
+        assert!(zone_prefix(&full).is_err());
+    }
+
+    #[test]
+    fn addresses_outside_the_zone() {
+        let zone = "113.0.203.in-addr.arpa.";
+        let prefix = zone_prefix(zone).unwrap();
+        assert!(prefix.contains(&ip("203.0.113.255")));
+        assert!(!prefix.contains(&ip("203.0.114.1")));
+        assert!(!reverse_name(ip("203.0.114.1")).ends_with(&format!(".{zone}")));
+
+        let zone = "0.8.b.d.0.1.0.0.2.ip6.arpa.";
+        let prefix = zone_prefix(zone).unwrap();
+        assert!(prefix.contains(&ip("2001:db8:fff::1")));
+        assert!(!prefix.contains(&ip("2001:db8:1000::1")));
+        assert!(!reverse_name(ip("2001:db8:1000::1")).ends_with(&format!(".{zone}")));
+        // Delegations are matched by containment, so a zone for a wider prefix
+        // than was delegated is refused
+        assert!(net("2001:db8::/32").contains(&prefix));
+        assert!(!net("2001:db8::/40").contains(&prefix));
+    }
+
+    #[test]
+    fn reverse_zones() {
+        assert!(is_reverse_zone("113.0.203.in-addr.arpa."));
+        assert!(is_reverse_zone("8.b.d.0.1.0.0.2.IP6.ARPA."));
+        assert!(!is_reverse_zone("in-addr.arpa."));
+        assert!(!is_reverse_zone("example.com."));
+    }
+}
@@ -0,0 +1,201 @@
+use crate::db::models::Record;
+use crate::dns::names::{is_subdomain, is_wildcard, same_name};
+use crate::features;
+
+/// A record about to be written to a zone, after its content has been validated
+pub struct Candidate<'a> {
+    pub name: &'a str,
+    pub record_type: &'a str,
+    pub content: &'a str,
+    pub ttl: i32,
+}
+
+/// Returns true for the NS records at the zone apex, which are managed by us
+/// rather than the zone owner
+pub fn is_apex_ns(zone_id: &str, name: &str, record_type: &str) -> bool {
+    record_type == "NS" && same_name(name, zone_id)
+}
+
+/// Returns true for records that are managed by us and can't be edited through
+/// the records API, including the A/AAAA records synthesized from an ALIAS
+pub fn is_managed(zone_id: &str, record: &Record) -> bool {
+    record.alias_id.is_some()
+        || record.record_type == "SOA"
+        || is_apex_ns(zone_id, &record.name, &record.record_type)
+}
+
+/// Checks a TTL against the configured limits, returning it in the form we
+/// store. The upper limit is capped at `i32::MAX` since that's what fits in the
+/// `ttl` column.
+pub fn validate_ttl(ttl: u32) -> Result<i32, String> {
+    let (min, max) = (*features::MIN_TTL, *features::MAX_TTL);
+    if ttl < min || ttl > max {
+        return Err(format!(
+            "TTL must be between {min} and {max} seconds, got {ttl}"
+        ));
+    }
+    Ok(ttl as i32)
+}
+
+/// Checks that writing `candidate` keeps the zone consistent. `existing` must
+/// not include the record being replaced, if any.
+pub fn check_record(
+    zone_id: &str,
+    existing: &[Record],
+    candidate: &Candidate,
+) -> Result<(), String> {
+    check_wildcard(candidate)?;
+    check_delegation(zone_id, existing, candidate)?;
+    check_cname(zone_id, existing, candidate)?;
+    check_alias(existing, candidate)?;
+    check_rrset(existing, candidate)
+}
+
+/// Wildcard NS and DNAME records have undefined or surprising behaviour
+/// (RFC 4592 4.2, 4.4), so they aren't allowed
+fn check_wildcard(candidate: &Candidate) -> Result<(), String> {
+    if is_wildcard(candidate.name) && matches!(candidate.record_type, "NS" | "DNAME") {
+        return Err(format!(
+            "{} records can't be created at a wildcard name",
+            candidate.record_type
+        ));
+    }
+    Ok(())
+}
+
+/// A CNAME can't share its name with any other record (RFC 1034 3.6.2), which
+/// also rules it out at the apex where the SOA and NS records live
+fn check_cname(zone_id: &str, existing: &[Record], candidate: &Candidate) -> Result<(), String> {
+    if candidate.record_type == "CNAME" && same_name(candidate.name, zone_id) {
+        return Err(String::from(
+            "A CNAME record can't be created at the zone apex",
+        ));
+    }
+
+    let conflict = existing.iter().find(|r| {
+        same_name(&r.name, candidate.name)
+            && (candidate.record_type == "CNAME" || r.record_type == "CNAME")
+    });
+    match conflict {
+        Some(record) => Err(format!(
+            "{} already has a {} record, a CNAME can't coexist with other records",
+            candidate.name, record.record_type
+        )),
+        None => Ok(()),
+    }
+}
+
+/// An ALIAS stands in for the A and AAAA records at its name, so it can't
+/// coexist with them or with another ALIAS
+fn check_alias(existing: &[Record], candidate: &Candidate) -> Result<(), String> {
+    let conflict = existing.iter().find(|r| {
+        same_name(&r.name, candidate.name)
+            && r.alias_id.is_none()
+            && match candidate.record_type {
+                "ALIAS" => matches!(r.record_type.as_str(), "A" | "AAAA" | "ALIAS"),
+                "A" | "AAAA" => r.record_type == "ALIAS",
+                _ => false,
+            }
+    });
+    match conflict {
+        Some(record) => Err(format!(
+            "{} already has a {} record, an ALIAS can't coexist with A, AAAA or other ALIAS records",
+            candidate.name, record.record_type
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Records with the same name and type form an RRset, which must share a TTL
+/// and can't contain the same data twice
+fn check_rrset(existing: &[Record], candidate: &Candidate) -> Result<(), String> {
+    let rrset = existing
+        .iter()
+        .filter(|r| same_name(&r.name, candidate.name) && r.record_type == candidate.record_type);
+
+    for record in rrset {
+        if record.content == candidate.content {
+            return Err(format!(
+                "An identical {} record already exists at {}",
+                candidate.record_type, candidate.name
+            ));
+        }
+        if record.ttl != candidate.ttl {
+            return Err(format!(
+                "All {} records at {} must have the same TTL ({}). Change the TTL of \
+                 the whole RRset with PUT /zones/<zone>/rrset or POST /zones/<zone>/changes",
+                candidate.record_type, candidate.name, record.ttl
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Subdomains may be delegated elsewhere with NS records. Only NS and DS records
+/// may live at the delegation point, and only glue A/AAAA records for the
+/// delegated nameservers may live below it.
+fn check_delegation(
+    zone_id: &str,
+    existing: &[Record],
+    candidate: &Candidate,
+) -> Result<(), String> {
+    if is_apex_ns(zone_id, candidate.name, candidate.record_type) {
+        return Err(String::from(
+            "The NS records at the zone apex are managed by HOSTSdotTXT",
+        ));
+    }
+
+    if candidate.record_type == "NS" {
+        // Glue is only allowed for the nameservers of this cut, including the
+        // one being added
+        let is_nameserver = |name: &str| {
+            same_name(name, candidate.content)
+                || existing.iter().any(|ns| {
+                    ns.record_type == "NS"
+                        && same_name(&ns.name, candidate.name)
+                        && same_name(&ns.content, name)
+                })
+        };
+        let occluded = existing.iter().find(|r| {
+            (same_name(&r.name, candidate.name) && !matches!(r.record_type.as_str(), "NS" | "DS"))
+                || (is_subdomain(&r.name, candidate.name)
+                    && !(matches!(r.record_type.as_str(), "A" | "AAAA") && is_nameserver(&r.name)))
+        });
+        if let Some(record) = occluded {
+            return Err(format!(
+                "Can't delegate {} while it has a {} record at {}",
+                candidate.name, record.record_type, record.name
+            ));
+        }
+    }
+
+    let cuts = existing
+        .iter()
+        .filter(|r| r.record_type == "NS" && !same_name(&r.name, zone_id));
+
+    for cut in cuts {
+        if same_name(&cut.name, candidate.name) && !matches!(candidate.record_type, "NS" | "DS") {
+            return Err(format!(
+                "{} is delegated, only NS and DS records may exist at a delegation point",
+                candidate.name
+            ));
+        }
+        if is_subdomain(candidate.name, &cut.name) {
+            let is_glue = matches!(candidate.record_type, "A" | "AAAA")
+                && existing.iter().any(|ns| {
+                    ns.record_type == "NS"
+                        && same_name(&ns.name, &cut.name)
+                        && same_name(&ns.content, candidate.name)
+                });
+            if !is_glue {
+                return Err(format!(
+                    "{} is delegated, only glue A/AAAA records for its nameservers may exist below it",
+                    cut.name
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use crate::dns::names;
+use chrono::{Datelike, Utc};
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+pub const DEFAULT_REFRESH: u32 = 7200;
+pub const DEFAULT_RETRY: u32 = 3600;
+pub const DEFAULT_EXPIRE: u32 = 1209600;
+pub const DEFAULT_MINIMUM: u32 = 3600;
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Soa {
+    pub mname: String,
+    pub rname: String,
+    pub serial: u32,
+    pub refresh: u32,
+    pub retry: u32,
+    pub expire: u32,
+    pub minimum: u32,
+}
+
+impl Soa {
+    pub fn new(zone_id: &str, mname: &str) -> Self {
+        Soa {
+            mname: mname.to_owned(),
+            rname: format!("hostmaster.{zone_id}"),
+            serial: next_serial(0),
+            refresh: DEFAULT_REFRESH,
+            retry: DEFAULT_RETRY,
+            expire: DEFAULT_EXPIRE,
+            minimum: DEFAULT_MINIMUM,
+        }
+    }
+
+    /// Checks the timers against the ranges recommended by RFC 1912, and that the
+    /// names will survive being written out and parsed back in
+    pub fn validate(&self) -> Result<(), String> {
+        for (field, name) in [("mname", &self.mname), ("rname", &self.rname)] {
+            let mut labels = name.strip_suffix('.').unwrap_or(name).split('.');
+            if name.chars().any(char::is_whitespace) || labels.any(str::is_empty) {
+                return Err(format!("Invalid SOA {field} `{name}`"));
+            }
+        }
+        if !(300..=86400).contains(&self.refresh) {
+            return Err(String::from(
+                "SOA refresh must be between 300 and 86400 seconds",
+            ));
+        }
+        if !(60..=86400).contains(&self.retry) || self.retry >= self.refresh {
+            return Err(String::from(
+                "SOA retry must be between 60 and 86400 seconds and less than refresh",
+            ));
+        }
+        if !(86400..=2419200).contains(&self.expire) || self.expire <= self.refresh + self.retry {
+            return Err(String::from(
+                "SOA expire must be between 86400 and 2419200 seconds and greater than refresh plus retry",
+            ));
+        }
+        if !(60..=86400).contains(&self.minimum) {
+            return Err(String::from(
+                "SOA minimum must be between 60 and 86400 seconds",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Soa {
+    type Err = String;
+
+    fn from_str(content: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = content.split_whitespace().collect();
+        if fields.len() != 7 {
+            return Err(format!("Invalid SOA record `{content}`"));
+        }
+        let number = |field: &str| {
+            field
+                .parse::<u32>()
+                .map_err(|_| format!("Invalid SOA record `{content}`"))
+        };
+        Ok(Soa {
+            mname: fields[0].to_owned(),
+            rname: fields[1].to_owned(),
+            serial: number(fields[2])?,
+            refresh: number(fields[3])?,
+            retry: number(fields[4])?,
+            expire: number(fields[5])?,
+            minimum: number(fields[6])?,
+        })
+    }
+}
+
+impl fmt::Display for Soa {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} {} {}",
+            self.mname,
+            self.rname,
+            self.serial,
+            self.refresh,
+            self.retry,
+            self.expire,
+            self.minimum
+        )
+    }
+}
+
+/// Returns the serial following `serial` in YYYYMMDDnn format. Once a day has
+/// used up all 100 revisions the serial keeps counting up into the next day, so
+/// it always increases.
+pub fn next_serial(serial: u32) -> u32 {
+    let now = Utc::now();
+    let base = now.year() as u32 * 1000000 + now.month() * 10000 + now.day() * 100;
+    match serial < base {
+        true => base,
+        false => serial.wrapping_add(1),
+    }
+}
+
+/// Converts an email address into the mailbox form used by SOA records, e.g.
+/// `dns.admin@example.com` becomes `dns\.admin.example.com.`
+pub fn rname_from_email(email: &str) -> Result<String, String> {
+    let invalid = || format!("Invalid SOA rname `{email}`");
+    let (local, domain) = email.split_once('@').ok_or_else(invalid)?;
+    // The dots in the local part are escaped, so it ends up as a single label
+    if local.is_empty() || local.split('.').any(str::is_empty) || local.len() > 63 {
+        return Err(invalid());
+    }
+    for part in local.split('.') {
+        names::check_label(part.as_bytes())?;
+    }
+    names::check_hostname(domain)?;
+    Ok(format!(
+        "{}.{}.",
+        local.replace('.', "\\."),
+        domain.trim_end_matches('.')
+    ))
+}
@@ -0,0 +1,160 @@
+use crate::db::models::Record;
+
+/// Lines of unchanged context kept around each hunk of a diff
+const CONTEXT: usize = 3;
+
+/// Formats a record as a line of a zone file
+pub fn record_line(record: &Record) -> String {
+    format!(
+        "{} {} IN {} {}",
+        record.name, record.ttl, record.record_type, record.content
+    )
+}
+
+/// The lines of a zone file for `records`, sorted so the same records always
+/// give the same file
+pub fn zone_lines(records: &[Record]) -> Vec<String> {
+    let mut lines: Vec<String> = records.iter().map(record_line).collect();
+    lines.sort();
+    lines.dedup();
+    lines
+}
+
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Produces a unified diff between two sorted zone files. Since both sides are
+/// sorted a merge of the two is already the shortest diff.
+pub fn unified_diff(from_label: &str, to_label: &str, old: &[String], new: &[String]) -> String {
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if j == new.len() || (i < old.len() && old[i] < new[j]) {
+            lines.push(Line::Removed(&old[i]));
+            i += 1;
+        } else if i == old.len() || new[j] < old[i] {
+            lines.push(Line::Added(&new[j]));
+            j += 1;
+        } else {
+            lines.push(Line::Same(&old[i]));
+            i += 1;
+            j += 1;
+        }
+    }
+
+    let mut out = format!("--- {from_label}\n+++ {to_label}\n");
+    let changed: Vec<usize> = (0..lines.len())
+        .filter(|&n| !matches!(lines[n], Line::Same(_)))
+        .collect();
+    let mut n = 0;
+    while n < changed.len() {
+        // Grow the hunk while the next change is close enough to share context
+        let mut last = n;
+        while last + 1 < changed.len() && changed[last + 1] - changed[last] <= 2 * CONTEXT {
+            last += 1;
+        }
+        let start = changed[n].saturating_sub(CONTEXT);
+        let end = (changed[last] + CONTEXT + 1).min(lines.len());
+
+        let count = |keep: fn(&Line) -> bool, range: std::ops::Range<usize>| {
+            lines[range].iter().filter(|l| keep(l)).count()
+        };
+        let in_old = |l: &Line| !matches!(l, Line::Added(_));
+        let in_new = |l: &Line| !matches!(l, Line::Removed(_));
+        let (old_start, old_len) = (count(in_old, 0..start) + 1, count(in_old, start..end));
+        let (new_start, new_len) = (count(in_new, 0..start) + 1, count(in_new, start..end));
+        // An empty side is numbered by the line before it
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            if old_len == 0 {
+                old_start - 1
+            } else {
+                old_start
+            },
+            old_len,
+            if new_len == 0 {
+                new_start - 1
+            } else {
+                new_start
+            },
+            new_len
+        ));
+        for line in &lines[start..end] {
+            match line {
+                Line::Same(l) => out.push_str(&format!(" {l}\n")),
+                Line::Removed(l) => out.push_str(&format!("-{l}\n")),
+                Line::Added(l) => out.push_str(&format!("+{l}\n")),
+            }
+        }
+        n = last + 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn identical_files_have_no_hunks() {
+        let file = lines(&["a", "b", "c"]);
+        assert_eq!(
+            unified_diff("old", "new", &file, &file),
+            "--- old\n+++ new\n"
+        );
+        assert_eq!(unified_diff("old", "new", &[], &[]), "--- old\n+++ new\n");
+    }
+
+    #[test]
+    fn pure_additions() {
+        assert_eq!(
+            unified_diff("old", "new", &[], &lines(&["a", "b"])),
+            "--- old\n+++ new\n@@ -0,0 +1,2 @@\n+a\n+b\n"
+        );
+        assert_eq!(
+            unified_diff("old", "new", &lines(&["a", "c"]), &lines(&["a", "b", "c"])),
+            "--- old\n+++ new\n@@ -1,2 +1,3 @@\n a\n+b\n c\n"
+        );
+    }
+
+    #[test]
+    fn pure_deletions() {
+        assert_eq!(
+            unified_diff("old", "new", &lines(&["a", "b"]), &[]),
+            "--- old\n+++ new\n@@ -1,2 +0,0 @@\n-a\n-b\n"
+        );
+        assert_eq!(
+            unified_diff("old", "new", &lines(&["a", "b", "c"]), &lines(&["a", "c"])),
+            "--- old\n+++ new\n@@ -1,3 +1,2 @@\n a\n-b\n c\n"
+        );
+    }
+
+    #[test]
+    fn distant_changes_get_their_own_hunks() {
+        let old = lines(&["a", "b", "c", "d", "e", "f", "g", "h", "i", "j"]);
+        let new = lines(&["b", "c", "d", "e", "f", "g", "h", "i", "j", "k"]);
+        assert_eq!(
+            unified_diff("old", "new", &old, &new),
+            "--- old\n+++ new\n\
+             @@ -1,4 +1,3 @@\n-a\n b\n c\n d\n\
+             @@ -8,3 +7,4 @@\n h\n i\n j\n+k\n"
+        );
+    }
+
+    #[test]
+    fn close_changes_share_a_hunk() {
+        let old = lines(&["a", "b", "c", "d", "e"]);
+        let new = lines(&["b", "c", "d", "e", "f"]);
+        assert_eq!(
+            unified_diff("old", "new", &old, &new),
+            "--- old\n+++ new\n@@ -1,5 +1,5 @@\n-a\n b\n c\n d\n e\n+f\n"
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::db::models::Actor;
 use axum::async_trait;
 use axum::extract::ConnectInfo;
 use axum::extract::FromRequest;
@@ -69,17 +70,36 @@ where
                     let digest = hasher.finalize();
                     let hash = hex::encode(digest);
 
+                    let client_ip = ClientIp::from_request(req).await.unwrap().0;
                     let db_pool = req.extensions().get::<Arc<Pool<Postgres>>>().unwrap();
+                    // Enough of the hash to tell keys apart without revealing it
+                    let details = json!({ "key": &hash[..8] });
 
                     let user = match crate::db::users::get_user_from_api_key(db_pool, &hash).await {
                         Ok(user) => user,
                         Err(err) => {
+                            let actor = Actor {
+                                user_id: None,
+                                client_ip,
+                            };
+                            crate::db::audit::record(
+                                db_pool,
+                                &actor,
+                                "api_key.use",
+                                false,
+                                details,
+                            );
                             return Err((
                                 StatusCode::INTERNAL_SERVER_ERROR,
                                 Json(json!({"error": err.to_string()})),
-                            ))
+                            ));
                         }
                     };
+                    let actor = Actor {
+                        user_id: Some(user.id),
+                        client_ip,
+                    };
+                    crate::db::audit::record(db_pool, &actor, "api_key.use", true, details);
                     let token = Token {
                         iss: "hostsdottxt".to_owned(),
                         sub: user.id,
@@ -209,6 +229,64 @@ where
     }
 }
 
+/// The entity tags listed in an `If-Match` header. A request without the header
+/// matches anything, so clients that don't send it keep last-write-wins.
+pub struct IfMatch(pub Option<Vec<String>>);
+
+impl IfMatch {
+    pub fn matches(&self, etag: &str) -> bool {
+        match &self.0 {
+            Some(tags) => tags.iter().any(|tag| tag == "*" || tag == etag),
+            None => true,
+        }
+    }
+
+    /// Fails with 412 Precondition Failed if `etag` isn't one of the listed tags
+    pub fn check(&self, etag: &str) -> Result<(), (StatusCode, Json<Value>)> {
+        match self.matches(etag) {
+            true => Ok(()),
+            false => Err((
+                StatusCode::PRECONDITION_FAILED,
+                Json(json!({
+                    "error": "The resource has been modified since it was last fetched",
+                    "etag": etag
+                })),
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl<B> FromRequest<B> for IfMatch
+where
+    B: Send,
+{
+    type Rejection = (StatusCode, Json<Value>);
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let values = req.headers().get_all(header::IF_MATCH);
+        if values.iter().next().is_none() {
+            return Ok(IfMatch(None));
+        }
+        let mut tags = Vec::new();
+        for value in values {
+            let value = value.to_str().map_err(|_| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": "Invalid If-Match header"})),
+                )
+            })?;
+            tags.extend(
+                value
+                    .split(',')
+                    .map(|tag| tag.trim().to_owned())
+                    .filter(|tag| !tag.is_empty()),
+            );
+        }
+        Ok(IfMatch(Some(tags)))
+    }
+}
+
 pub struct ClientIp(pub Option<IpAddr>);
 
 #[async_trait]
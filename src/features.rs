@@ -8,4 +8,28 @@ lazy_static! {
         env::var("TOTP_ENABLED").unwrap_or_else(|_| String::from("false")) == "true";
     pub static ref METRICS_ENABLED: bool = env::var("METRICS_URL").is_ok()
         && env::var("METRICS_ENABLED").unwrap_or_else(|_| String::from("false")) == "true";
+    pub static ref ALIAS_REFRESH_INTERVAL: u64 = env::var("ALIAS_REFRESH_INTERVAL")
+        .ok()
+        .and_then(|interval| interval.parse().ok())
+        .filter(|interval| *interval > 0)
+        .unwrap_or(60);
+    pub static ref SCHEDULED_CHANGES_INTERVAL: u64 = env::var("SCHEDULED_CHANGES_INTERVAL")
+        .ok()
+        .and_then(|interval| interval.parse().ok())
+        .filter(|interval| *interval > 0)
+        .unwrap_or(30);
+    pub static ref EXPIRY_SWEEP_INTERVAL: u64 = env::var("EXPIRY_SWEEP_INTERVAL")
+        .ok()
+        .and_then(|interval| interval.parse().ok())
+        .filter(|interval| *interval > 0)
+        .unwrap_or(60);
+    pub static ref MIN_TTL: u32 = env::var("MIN_TTL")
+        .ok()
+        .and_then(|ttl| ttl.parse().ok())
+        .unwrap_or(60);
+    pub static ref MAX_TTL: u32 = env::var("MAX_TTL")
+        .ok()
+        .and_then(|ttl| ttl.parse().ok())
+        .unwrap_or(604800)
+        .min(i32::MAX as u32);
 }
@@ -1,6 +1,6 @@
 use axum::extract::Extension;
 use axum::{
-    routing::{get, post, put},
+    routing::{delete, get, post, put},
     Router, Server,
 };
 use dotenvy::dotenv;
@@ -9,13 +9,16 @@ use sqlx::postgres::PgPoolOptions;
 use std::env;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tower::ServiceBuilder;
 use tower_http::trace::TraceLayer;
 
 mod db;
+mod dns;
 mod extractors;
 mod features;
 mod routes;
+mod tasks;
 
 #[tokio::main]
 async fn main() {
@@ -32,6 +35,12 @@ async fn main() {
 
     println!("SIGNUPS_ENABLED = {}", *features::SIGNUPS_ENABLED);
     println!("TOTP_ENABLED = {}", *features::TOTP_ENABLED);
+    println!("MIN_TTL = {}", *features::MIN_TTL);
+    println!("MAX_TTL = {}", *features::MAX_TTL);
+    if *features::MIN_TTL > *features::MAX_TTL {
+        eprintln!("MIN_TTL can't be greater than MAX_TTL");
+        std::process::exit(1);
+    }
 
     // Set logging levels if not already set
     if env::var_os("RUST_LOG").is_none() {
@@ -62,6 +71,20 @@ async fn main() {
     };
     info!("Metrics pool (possibly) initialized");
 
+    tokio::spawn(tasks::alias::run(
+        pg_pool.clone(),
+        Arc::new(tasks::alias::SystemResolver),
+        Duration::from_secs(*features::ALIAS_REFRESH_INTERVAL),
+    ));
+    tokio::spawn(tasks::scheduled::run(
+        pg_pool.clone(),
+        Duration::from_secs(*features::SCHEDULED_CHANGES_INTERVAL),
+    ));
+    tokio::spawn(tasks::expiry::run(
+        pg_pool.clone(),
+        Duration::from_secs(*features::EXPIRY_SWEEP_INTERVAL),
+    ));
+
     // Create our WhoIs client
     let whois_client = whois_rust::WhoIs::from_string(include_str!(concat!(
         env!("CARGO_MANIFEST_DIR"),
@@ -77,6 +100,20 @@ async fn main() {
                 Router::new()
                     .route("/features", get(routes::v1::features::get_features))
                     .route("/metrics", get(routes::v1::metrics::get_metrics))
+                    .route("/audit", get(routes::v1::audit::get_events))
+                    .nest(
+                        "/delegations",
+                        Router::new()
+                            .route(
+                                "/",
+                                get(routes::v1::delegations::list_delegations)
+                                    .post(routes::v1::delegations::create_delegation),
+                            )
+                            .route(
+                                "/:delegation_id",
+                                delete(routes::v1::delegations::delete_delegation),
+                            ),
+                    )
                     .nest(
                         "/users",
                         Router::new()
@@ -91,15 +128,64 @@ async fn main() {
                         Router::new()
                             .route("/", get(routes::v1::zones::list_zones))
                             .route("/root", get(routes::v1::zones::get_root_domain))
+                            .route("/reverse", get(routes::v1::zones::get_reverse_name))
                             .route(
                                 "/:zone_id",
                                 get(routes::v1::records::get_records)
                                     .post(routes::v1::zones::create_zone)
-                                    .put(routes::v1::records::create_record),
+                                    .put(routes::v1::records::create_record)
+                                    .patch(routes::v1::zones::update_zone),
+                            )
+                            .route(
+                                "/:zone_id/changes",
+                                post(routes::v1::records::apply_changes),
+                            )
+                            .route("/:zone_id/rrset", put(routes::v1::records::set_rrset))
+                            .route(
+                                "/:zone_id/history",
+                                get(routes::v1::history::get_zone_history),
+                            )
+                            .route("/:zone_id/diff", get(routes::v1::history::get_diff))
+                            .route(
+                                "/:zone_id/scheduled",
+                                get(routes::v1::scheduled::list_scheduled_changes)
+                                    .post(routes::v1::scheduled::create_scheduled_change),
+                            )
+                            .route(
+                                "/:zone_id/scheduled/:scheduled_id",
+                                delete(routes::v1::scheduled::cancel_scheduled_change),
+                            )
+                            .route(
+                                "/:zone_id/members",
+                                get(routes::v1::members::list_members)
+                                    .post(routes::v1::members::add_member),
+                            )
+                            .route(
+                                "/:zone_id/members/:user_id",
+                                delete(routes::v1::members::remove_member),
+                            )
+                            .route("/:zone_id/restore", post(routes::v1::history::restore_zone))
+                            .route(
+                                "/:zone_id/soa",
+                                get(routes::v1::zones::get_soa).put(routes::v1::zones::update_soa),
+                            )
+                            .route(
+                                "/:zone_id/:record_id/history",
+                                get(routes::v1::history::get_record_history),
+                            )
+                            .route(
+                                "/:zone_id/:record_id/enable",
+                                post(routes::v1::records::enable_record),
+                            )
+                            .route(
+                                "/:zone_id/:record_id/disable",
+                                post(routes::v1::records::disable_record),
                             )
                             .route(
                                 "/:zone_id/:record_id",
-                                put(routes::v1::records::update_record)
+                                get(routes::v1::records::get_record)
+                                    .put(routes::v1::records::update_record)
+                                    .patch(routes::v1::records::patch_record)
                                     .delete(routes::v1::records::delete_record),
                             ),
                     ),
@@ -0,0 +1,62 @@
+use crate::db;
+use crate::db::audit::AuditFilter;
+use crate::extractors::{Json, Jwt};
+use axum::extract::Query;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::types::Uuid;
+use sqlx::{Pool, Postgres};
+use std::sync::Arc;
+
+/// Largest page of events returned at once
+const MAX_PAGE_SIZE: i64 = 1000;
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    user_id: Option<Uuid>,
+    event: Option<String>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    before: Option<i64>,
+    limit: Option<i64>,
+}
+
+/// Lists audit events, newest first. Users only see their own events, admins
+/// can see everyone's and filter by `user_id`.
+pub async fn get_events(
+    Query(query): Query<AuditQuery>,
+    Jwt(user): Jwt,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> impl IntoResponse {
+    let user_id = match (user.admin, query.user_id) {
+        (true, user_id) => user_id,
+        (false, None) => Some(user.sub),
+        (false, Some(user_id)) if user_id == user.sub => Some(user_id),
+        (false, Some(_)) => {
+            return (
+                StatusCode::FORBIDDEN,
+                Json(json!({"error": "You do not have permission to perform this action"})),
+            )
+        }
+    };
+
+    let filter = AuditFilter {
+        user_id,
+        event: query.event,
+        since: query.since,
+        until: query.until,
+        before: query.before.unwrap_or(i64::MAX),
+        limit: query.limit.unwrap_or(100).clamp(1, MAX_PAGE_SIZE),
+    };
+    match db::audit::get_events(&pool, &filter).await {
+        Ok(events) => (StatusCode::OK, Json(json!(events))),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": err.to_string()})),
+        ),
+    }
+}
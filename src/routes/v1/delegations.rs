@@ -0,0 +1,151 @@
+use crate::db;
+use crate::db::models::Actor;
+use crate::extractors::{ClientIp, Json, Jwt};
+use crate::routes::v1::requests;
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use ipnet::IpNet;
+use serde_json::json;
+use sqlx::{Error, Pool, Postgres};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub async fn list_delegations(
+    Jwt(user): Jwt,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> impl IntoResponse {
+    let delegations = match user.admin {
+        true => db::delegations::get_all_delegations(&pool).await,
+        false => db::delegations::get_delegations(&pool, user.sub).await,
+    };
+    match delegations {
+        Ok(delegations) => (StatusCode::OK, Json(json!(delegations))),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": err.to_string()})),
+        ),
+    }
+}
+
+pub async fn create_delegation(
+    Jwt(user): Jwt,
+    ClientIp(client_ip): ClientIp,
+    Json(data): Json<requests::Delegation>,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> impl IntoResponse {
+    if !user.admin {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "You do not have permission to perform this action"})),
+        );
+    }
+
+    let prefix = match data.prefix.parse::<IpNet>() {
+        Ok(prefix) => prefix,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid prefix"})),
+            )
+        }
+    };
+    if prefix.trunc() != prefix {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": "Invalid prefix",
+                "message": format!("Prefix has host bits set, did you mean {}?", prefix.trunc())
+            })),
+        );
+    }
+
+    let owner = match db::users::get_user(&pool, &data.owner_email).await {
+        Ok(owner) => owner,
+        Err(Error::RowNotFound) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "User not found"})),
+            )
+        }
+        Err(err) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": err.to_string()})),
+            )
+        }
+    };
+
+    let delegation = db::delegations::create_delegation(&pool, &prefix.to_string(), owner.id).await;
+    match delegation {
+        Ok(delegation) => {
+            let actor = Actor {
+                user_id: Some(user.sub),
+                client_ip,
+            };
+            let details = json!({"id": delegation.id, "prefix": delegation.prefix, "owner_uuid": delegation.owner_uuid});
+            db::audit::record(&pool, &actor, "delegation.create", true, details);
+            (StatusCode::OK, Json(json!(delegation)))
+        }
+        Err(Error::Database(e))
+            if e.code().unwrap_or(std::borrow::Cow::Borrowed("")) == "23505" =>
+        {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "That prefix is already delegated"})),
+            )
+        }
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": err.to_string()})),
+        ),
+    }
+}
+
+pub async fn delete_delegation(
+    Path(delegation_id): Path<String>,
+    Jwt(user): Jwt,
+    ClientIp(client_ip): ClientIp,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> impl IntoResponse {
+    if !user.admin {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "You do not have permission to perform this action"})),
+        );
+    }
+
+    let delegation_id = match Uuid::parse_str(&delegation_id) {
+        Ok(id) => id,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid delegation id"})),
+            )
+        }
+    };
+
+    match db::delegations::delete_delegation(&pool, &delegation_id).await {
+        Ok(0) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Delegation not found"})),
+        ),
+        Ok(_) => {
+            let actor = Actor {
+                user_id: Some(user.sub),
+                client_ip,
+            };
+            let details = json!({ "id": delegation_id });
+            db::audit::record(&pool, &actor, "delegation.delete", true, details);
+            (
+                StatusCode::OK,
+                Json(json!({ "message": format!("Delegation {} deleted", delegation_id) })),
+            )
+        }
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": err.to_string()})),
+        ),
+    }
+}
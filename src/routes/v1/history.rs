@@ -0,0 +1,618 @@
+use crate::db;
+use crate::db::models::{Actor, Record, RecordHistory, Role, Zone};
+use crate::db::records::Change;
+use crate::dns::{idn, rules, zonefile};
+use crate::extractors::{ClientIp, Json, Jwt};
+use crate::routes::v1::{members, records, requests, zones};
+use axum::extract::{Path, Query};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::types::Uuid;
+use sqlx::{Error, Pool, Postgres};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Largest page of zone history returned at once
+const MAX_PAGE_SIZE: i64 = 1000;
+
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+    limit: Option<i64>,
+    /// Only return entries older than this entry id, for paging back through
+    /// the history
+    before: Option<i64>,
+}
+
+/// Lists the changes made to records in a zone, newest first
+pub async fn get_zone_history(
+    Path(zone_id): Path<String>,
+    Query(query): Query<HistoryQuery>,
+    Jwt(user): Jwt,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> impl IntoResponse {
+    let zone = db::zones::get_zone(&pool, &zones::normalize_zone_id(&zone_id)).await;
+
+    if zone.is_err() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Zone not found"})),
+        );
+    }
+    let zone = zone.unwrap();
+
+    if let Err(e) = members::authorize(&pool, &zone, user.sub, Role::Viewer).await {
+        return e;
+    }
+
+    let limit = query.limit.unwrap_or(100).clamp(1, MAX_PAGE_SIZE);
+    let before = query.before.unwrap_or(i64::MAX);
+    match db::history::get_zone_history(&pool, &zone.id, before, limit).await {
+        Ok(history) => (StatusCode::OK, Json(json!(history))),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": err.to_string()})),
+        ),
+    }
+}
+
+/// Lists the changes made to a single record, newest first. This still works
+/// once the record has been deleted.
+pub async fn get_record_history(
+    Path((zone_id, record_id)): Path<(String, String)>,
+    Jwt(user): Jwt,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> impl IntoResponse {
+    let zone = db::zones::get_zone(&pool, &zones::normalize_zone_id(&zone_id)).await;
+
+    if zone.is_err() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Zone not found"})),
+        );
+    }
+    let zone = zone.unwrap();
+
+    if let Err(e) = members::authorize(&pool, &zone, user.sub, Role::Viewer).await {
+        return e;
+    }
+
+    let record_id = match Uuid::parse_str(&record_id) {
+        Ok(record_id) => record_id,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid record id"})),
+            )
+        }
+    };
+    let history = match db::history::get_record_history(&pool, &zone.id, &record_id).await {
+        Ok(history) => history,
+        Err(err) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": err.to_string()})),
+            )
+        }
+    };
+    // Records from before history was kept have none, but still exist
+    if history.is_empty() {
+        if let Err(Error::RowNotFound) = db::records::get_record(&pool, &zone.id, &record_id).await
+        {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Record not found"})),
+            );
+        }
+    }
+
+    (StatusCode::OK, Json(json!(history)))
+}
+
+/// Rolls the records of a zone back to how they were at the given time. The
+/// rollback is itself a set of changes, so it shows up in the history and can
+/// be undone by restoring again. It's checked like any other batch of changes,
+/// so nothing is written if the old records break the zone's rules.
+pub async fn restore_zone(
+    Path(zone_id): Path<String>,
+    Jwt(user): Jwt,
+    ClientIp(client_ip): ClientIp,
+    Json(data): Json<requests::Restore>,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> impl IntoResponse {
+    let zone = db::zones::get_zone(&pool, &zones::normalize_zone_id(&zone_id)).await;
+
+    if zone.is_err() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Zone not found"})),
+        );
+    }
+    let zone = zone.unwrap();
+
+    if let Err(e) = members::authorize(&pool, &zone, user.sub, Role::Editor).await {
+        return e;
+    }
+    if data.at > Utc::now() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "Can't restore to a time in the future"})),
+        );
+    }
+
+    let actor = Actor {
+        user_id: Some(user.sub),
+        client_ip,
+    };
+    let result = db::records::restore_zone(
+        &pool,
+        &actor,
+        &zone.id,
+        &data.at,
+        |zone, current, history| -> Result<_, (StatusCode, Json<Value>)> {
+            let past = state_at(&current, &history).map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({ "error": e })),
+                )
+            })?;
+            let (changes, restored): (Vec<_>, Vec<_>) =
+                restore_changes(zone, &current, past).into_iter().unzip();
+            // The rules may have changed since, or the old records may only have
+            // been valid alongside managed records that are kept as they are
+            let mut planned =
+                records::plan_changes(zone, current, changes).map_err(|(index, _, e)| {
+                    let record = &restored[index];
+                    let error = format!(
+                        "Can't restore the {} record at {}: {e}",
+                        record.record_type, record.name
+                    );
+                    (StatusCode::CONFLICT, Json(json!({ "error": error })))
+                })?;
+            // Batches can't set the enabled flag, so restored records get
+            // theirs back here
+            for (change, record) in planned.iter_mut().zip(&restored) {
+                if let Change::Create(data) | Change::Update { data, .. } = change {
+                    data.enabled = record.enabled;
+                }
+            }
+            Ok(planned)
+        },
+    )
+    .await;
+    let changes = match result {
+        Ok(Ok(results)) => results,
+        Ok(Err(e)) => return e,
+        Err(Error::RowNotFound) => {
+            return (
+                StatusCode::CONFLICT,
+                Json(json!({"error": "The zone changed while restoring it, nothing was applied"})),
+            )
+        }
+        Err(err) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": err.to_string()})),
+            )
+        }
+    };
+
+    let count = |f: fn(&Change) -> bool| changes.iter().filter(|(c, _)| f(c)).count();
+    (
+        StatusCode::OK,
+        Json(json!({
+            "created": count(|c| matches!(c, Change::Create(_))),
+            "updated": count(|c| matches!(c, Change::Update { .. })),
+            "deleted": count(|c| matches!(c, Change::Delete { .. })),
+        })),
+    )
+}
+
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    from: DateTime<Utc>,
+    /// Defaults to now
+    to: Option<DateTime<Utc>>,
+    /// `json` (the default) or `unified` for a unified diff of the zone file
+    format: Option<String>,
+}
+
+/// Compares the records of a zone at two points in time, using the record
+/// history to work out how the zone looked at each
+pub async fn get_diff(
+    Path(zone_id): Path<String>,
+    Query(query): Query<DiffQuery>,
+    Jwt(user): Jwt,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> Response {
+    let zone = db::zones::get_zone(&pool, &zones::normalize_zone_id(&zone_id)).await;
+
+    if zone.is_err() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Zone not found"})),
+        )
+            .into_response();
+    }
+    let zone = zone.unwrap();
+
+    if let Err(e) = members::authorize(&pool, &zone, user.sub, Role::Viewer).await {
+        return e.into_response();
+    }
+
+    let to = query.to.unwrap_or_else(Utc::now);
+    if query.from > to {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "`from` must be before `to`"})),
+        )
+            .into_response();
+    }
+    let unified = match query.format.as_deref() {
+        None | Some("json") => false,
+        Some("unified") => true,
+        Some(other) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": format!("Unknown format `{other}`") })),
+            )
+                .into_response()
+        }
+    };
+
+    let current = match db::records::get_records(&pool, &zone.id).await {
+        Ok(records) => records,
+        Err(err) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": err.to_string()})),
+            )
+                .into_response()
+        }
+    };
+    let history = match db::history::get_zone_history_since(&*pool, &zone.id, &query.from).await {
+        Ok(history) => history,
+        Err(err) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": err.to_string()})),
+            )
+                .into_response()
+        }
+    };
+    // `history` is newest first, so the changes made after `to` come first
+    let after_to = history.iter().take_while(|e| e.created_at > to).count();
+    let states = state_at(&current, &history[..after_to])
+        .and_then(|new| Ok((state_at(&current, &history)?, new)));
+    // Disabled records aren't served, so disabling one shows up as a removal
+    let (old, new) = match states {
+        Ok((old, new)) => (
+            old.into_iter()
+                .filter(|r| r.enabled && !rules::is_managed(&zone.id, r))
+                .collect::<Vec<_>>(),
+            new.into_iter()
+                .filter(|r| r.enabled && !rules::is_managed(&zone.id, r))
+                .collect::<Vec<_>>(),
+        ),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": e })),
+            )
+                .into_response()
+        }
+    };
+
+    if unified {
+        let diff = zonefile::unified_diff(
+            &format!("{} {}", zone.id, query.from.to_rfc3339()),
+            &format!("{} {}", zone.id, to.to_rfc3339()),
+            &zonefile::zone_lines(&old),
+            &zonefile::zone_lines(&new),
+        );
+        return (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+            diff,
+        )
+            .into_response();
+    }
+
+    let mut old: HashMap<Uuid, Record> = old.into_iter().map(|r| (r.id, r)).collect();
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    for record in new {
+        match old.remove(&record.id) {
+            None => added.push(idn::with_unicode(&record, "name")),
+            Some(before) if zonefile::record_line(&before) != zonefile::record_line(&record) => {
+                changed.push(json!({
+                    "old": idn::with_unicode(&before, "name"),
+                    "new": idn::with_unicode(&record, "name"),
+                }))
+            }
+            Some(_) => {}
+        }
+    }
+    let removed: Vec<Value> = old
+        .values()
+        .map(|record| idn::with_unicode(record, "name"))
+        .collect();
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "from": query.from,
+            "to": to,
+            "added": added,
+            "removed": removed,
+            "changed": changed,
+        })),
+    )
+        .into_response()
+}
+
+/// Works out the records of a zone as they were before the changes in
+/// `history` (newest first) by undoing them one by one from the current state
+fn state_at(current: &[Record], history: &[RecordHistory]) -> Result<Vec<Record>, String> {
+    let mut records: HashMap<Uuid, Record> = current.iter().map(|r| (r.id, r.clone())).collect();
+    for entry in history {
+        match (&entry.action[..], &entry.old_value) {
+            ("create", _) => {
+                records.remove(&entry.record_id);
+            }
+            (_, Some(old)) => {
+                let old: Record = serde_json::from_value(old.clone())
+                    .map_err(|e| format!("Invalid history entry {}: {e}", entry.id))?;
+                records.insert(entry.record_id, old);
+            }
+            (_, None) => return Err(format!("Invalid history entry {}", entry.id)),
+        }
+    }
+    Ok(records.into_values().collect())
+}
+
+/// The changes that turn `current` into `past`, each with the record it brings
+/// back or deletes. Managed records are left alone since they're kept in line
+/// with the zone by HOSTSdotTXT itself, and so are records that have expired
+/// since, which would only be swept away again.
+fn restore_changes(
+    zone: &Zone,
+    current: &[Record],
+    past: Vec<Record>,
+) -> Vec<(requests::Change, Record)> {
+    let now = Utc::now();
+    let mut past: HashMap<Uuid, Record> = past
+        .into_iter()
+        .filter(|r| {
+            let expired = match r.expires_at {
+                Some(expires_at) => expires_at <= now,
+                None => false,
+            };
+            !rules::is_managed(&zone.id, r) && !expired
+        })
+        .map(|r| (r.id, r))
+        .collect();
+
+    let mut deletes = Vec::new();
+    let mut updates = Vec::new();
+    for record in current.iter().filter(|r| !rules::is_managed(&zone.id, r)) {
+        match past.remove(&record.id) {
+            None => deletes.push((
+                requests::Change::Delete {
+                    id: record.id.to_string(),
+                },
+                record.clone(),
+            )),
+            Some(old)
+                if old.name != record.name
+                    || old.record_type != record.record_type
+                    || old.content != record.content
+                    || old.ttl != record.ttl
+                    || old.expires_at != record.expires_at
+                    || old.comment != record.comment
+                    || old.tags != record.tags
+                    || old.enabled != record.enabled =>
+            {
+                let patch = requests::RecordPatch {
+                    name: Some(old.name.clone()),
+                    record_type: Some(old.record_type.clone()),
+                    content: Some(old.content.clone()),
+                    ttl: Some(old.ttl as u32),
+                    expires_at: Some(old.expires_at),
+                    comment: Some(old.comment.clone()),
+                    tags: Some(old.tags.clone()),
+                };
+                updates.push((
+                    requests::Change::Update {
+                        id: record.id.to_string(),
+                        patch,
+                    },
+                    old,
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+    // Deleted records come back with a new id
+    let creates = past.into_values().map(|old| {
+        let data = requests::Record {
+            name: old.name.clone(),
+            record_type: old.record_type.clone(),
+            content: old.content.clone(),
+            ttl: Some(old.ttl as u32),
+            expires_at: old.expires_at,
+            comment: old.comment.clone(),
+            tags: old.tags.clone(),
+        };
+        (requests::Change::Create(data), old)
+    });
+
+    deletes.into_iter().chain(updates).chain(creates).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    const ZONE: &str = "example.com.";
+
+    fn zone() -> Zone {
+        Zone {
+            id: String::from(ZONE),
+            owner_uuid: Uuid::new_v4(),
+            created_at: Utc::now(),
+            modified_at: Utc::now(),
+            default_ttl: 3600,
+        }
+    }
+
+    fn record(record_type: &str, name: &str, content: &str) -> Record {
+        Record {
+            id: Uuid::new_v4(),
+            zone_id: String::from(ZONE),
+            name: String::from(name),
+            record_type: String::from(record_type),
+            content: String::from(content),
+            ttl: 300,
+            created_at: Utc::now(),
+            modified_at: Utc::now(),
+            alias_id: None,
+            expires_at: None,
+            comment: None,
+            tags: vec![],
+            enabled: true,
+        }
+    }
+
+    fn entry(id: i64, action: &str, old: Option<&Record>, new: Option<&Record>) -> RecordHistory {
+        let record_id = old.or(new).map(|r| r.id).unwrap();
+        RecordHistory {
+            id,
+            zone_id: String::from(ZONE),
+            record_id,
+            action: String::from(action),
+            old_value: old.map(|r| serde_json::to_value(r).unwrap()),
+            new_value: new.map(|r| serde_json::to_value(r).unwrap()),
+            actor: None,
+            client_ip: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn contents(records: &[Record]) -> Vec<String> {
+        let mut contents: Vec<String> = records.iter().map(|r| r.content.clone()).collect();
+        contents.sort();
+        contents
+    }
+
+    #[test]
+    fn state_at_undoes_changes_newest_first() {
+        let v1 = record("A", "www.example.com.", "192.0.2.1");
+        let v2 = Record {
+            content: String::from("192.0.2.2"),
+            ..v1.clone()
+        };
+        let added = record("A", "mail.example.com.", "192.0.2.3");
+        let history = [
+            entry(3, "create", None, Some(&added)),
+            entry(2, "update", Some(&v1), Some(&v2)),
+        ];
+        let current = [v2.clone(), added];
+
+        let past = state_at(&current, &history).unwrap();
+        assert_eq!(contents(&past), ["192.0.2.1"]);
+        assert_eq!(past[0].id, v1.id);
+        // No history means nothing to undo
+        assert_eq!(contents(&state_at(&current, &[]).unwrap()).len(), 2);
+    }
+
+    #[test]
+    fn state_at_brings_back_deleted_records() {
+        let v1 = record("A", "www.example.com.", "192.0.2.1");
+        let v2 = Record {
+            content: String::from("192.0.2.2"),
+            ..v1.clone()
+        };
+        let history = [
+            entry(3, "delete", Some(&v2), None),
+            entry(2, "update", Some(&v1), Some(&v2)),
+        ];
+        // Undoing only the delete gives the record as it was last
+        assert_eq!(
+            contents(&state_at(&[], &history[..1]).unwrap()),
+            ["192.0.2.2"]
+        );
+        assert_eq!(contents(&state_at(&[], &history).unwrap()), ["192.0.2.1"]);
+    }
+
+    #[test]
+    fn state_at_rejects_entries_without_an_old_value() {
+        let record = record("A", "www.example.com.", "192.0.2.1");
+        let history = [entry(1, "update", None, Some(&record))];
+        assert!(state_at(&[record], &history).is_err());
+    }
+
+    #[test]
+    fn restore_recreates_records_deleted_since() {
+        let deleted = record("A", "www.example.com.", "192.0.2.1");
+        let changes = restore_changes(&zone(), &[], vec![deleted.clone()]);
+        assert_eq!(changes.len(), 1);
+        match &changes[0] {
+            (requests::Change::Create(data), old) => {
+                assert_eq!(data.content, "192.0.2.1");
+                assert_eq!(data.ttl, Some(300));
+                assert_eq!(old.id, deleted.id);
+            }
+            _ => panic!("expected a create"),
+        }
+    }
+
+    #[test]
+    fn restore_deletes_and_reverts_records() {
+        let kept = record("A", "www.example.com.", "192.0.2.1");
+        let changed = Record {
+            ttl: 60,
+            ..kept.clone()
+        };
+        let added = record("A", "mail.example.com.", "192.0.2.3");
+        let changes = restore_changes(&zone(), &[changed, added.clone()], vec![kept.clone()]);
+        assert_eq!(changes.len(), 2);
+        match &changes[0] {
+            (requests::Change::Delete { id }, _) => assert_eq!(id, &added.id.to_string()),
+            _ => panic!("expected a delete"),
+        }
+        match &changes[1] {
+            (requests::Change::Update { id, patch }, _) => {
+                assert_eq!(id, &kept.id.to_string());
+                assert_eq!(patch.ttl, Some(300));
+            }
+            _ => panic!("expected an update"),
+        }
+    }
+
+    #[test]
+    fn restore_leaves_unchanged_and_managed_records_alone() {
+        let kept = record("A", "www.example.com.", "192.0.2.1");
+        let soa = record("SOA", ZONE, "ns1. hostmaster. 2 3600 600 86400 300");
+        let old_soa = Record {
+            content: String::from("ns1. hostmaster. 1 3600 600 86400 300"),
+            ..soa.clone()
+        };
+        let apex_ns = record("NS", ZONE, "ns1.example.net.");
+        let changes = restore_changes(&zone(), &[kept.clone(), soa], vec![kept, old_soa, apex_ns]);
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn restore_skips_records_that_have_expired_since() {
+        let expired = Record {
+            expires_at: Some(Utc::now() - Duration::hours(1)),
+            ..record("A", "tmp.example.com.", "192.0.2.9")
+        };
+        assert!(restore_changes(&zone(), &[], vec![expired]).is_empty());
+    }
+}
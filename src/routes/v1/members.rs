@@ -0,0 +1,177 @@
+use crate::db;
+use crate::db::models::{Actor, Role, Zone};
+use crate::extractors::{ClientIp, Json, Jwt};
+use crate::routes::v1::{requests, zones};
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use serde_json::{json, Value};
+use sqlx::types::Uuid;
+use sqlx::{Error, Pool, Postgres};
+use std::sync::Arc;
+
+/// Checks that the user is a member of the zone with at least the `required`
+/// role, returning their role
+pub(crate) async fn authorize(
+    pool: &Pool<Postgres>,
+    zone: &Zone,
+    user_id: Uuid,
+    required: Role,
+) -> Result<Role, (StatusCode, Json<Value>)> {
+    match db::members::get_role(pool, &zone.id, user_id).await {
+        Ok(Some(role)) if role >= required => Ok(role),
+        Ok(_) => Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "You do not have permissions to access this zone"})),
+        )),
+        Err(err) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": err.to_string()})),
+        )),
+    }
+}
+
+pub async fn list_members(
+    Path(zone_id): Path<String>,
+    Jwt(user): Jwt,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> impl IntoResponse {
+    let zone = match db::zones::get_zone(&pool, &zones::normalize_zone_id(&zone_id)).await {
+        Ok(zone) => zone,
+        Err(_) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Zone not found"})),
+            )
+        }
+    };
+    if let Err(e) = authorize(&pool, &zone, user.sub, Role::Viewer).await {
+        return e;
+    }
+
+    match db::members::get_members(&pool, &zone.id).await {
+        Ok(members) => (StatusCode::OK, Json(json!(members))),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": err.to_string()})),
+        ),
+    }
+}
+
+/// Gives an existing user access to the zone, or changes the role of a member
+pub async fn add_member(
+    Path(zone_id): Path<String>,
+    Jwt(user): Jwt,
+    ClientIp(client_ip): ClientIp,
+    Json(data): Json<requests::Member>,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> impl IntoResponse {
+    let zone = match db::zones::get_zone(&pool, &zones::normalize_zone_id(&zone_id)).await {
+        Ok(zone) => zone,
+        Err(_) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Zone not found"})),
+            )
+        }
+    };
+    if let Err(e) = authorize(&pool, &zone, user.sub, Role::Owner).await {
+        return e;
+    }
+
+    let member = match db::users::get_user(&pool, &data.email).await {
+        Ok(member) => member,
+        Err(Error::RowNotFound) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "No user with that email"})),
+            )
+        }
+        Err(err) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": err.to_string()})),
+            )
+        }
+    };
+
+    match db::members::set_member(&pool, &zone.id, member.id, data.role).await {
+        Ok(Some(member)) => {
+            let actor = Actor {
+                user_id: Some(user.sub),
+                client_ip,
+            };
+            let details = json!({"zone": zone.id, "user_id": member.user_id, "role": member.role});
+            db::audit::record(&pool, &actor, "member.add", true, details);
+            (StatusCode::OK, Json(json!(member)))
+        }
+        Ok(None) => (
+            StatusCode::CONFLICT,
+            Json(json!({"error": "A zone must have at least one owner"})),
+        ),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": err.to_string()})),
+        ),
+    }
+}
+
+/// Removes a member from the zone. Owners can remove anyone, and any member can
+/// remove themselves.
+pub async fn remove_member(
+    Path((zone_id, user_id)): Path<(String, String)>,
+    Jwt(user): Jwt,
+    ClientIp(client_ip): ClientIp,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> impl IntoResponse {
+    let zone = match db::zones::get_zone(&pool, &zones::normalize_zone_id(&zone_id)).await {
+        Ok(zone) => zone,
+        Err(_) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Zone not found"})),
+            )
+        }
+    };
+    let user_id = match Uuid::parse_str(&user_id) {
+        Ok(id) => id,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid user id"})),
+            )
+        }
+    };
+    let required = match user_id == user.sub {
+        true => Role::Viewer,
+        false => Role::Owner,
+    };
+    if let Err(e) = authorize(&pool, &zone, user.sub, required).await {
+        return e;
+    }
+
+    match db::members::remove_member(&pool, &zone.id, user_id).await {
+        Ok(Some(member)) => {
+            let actor = Actor {
+                user_id: Some(user.sub),
+                client_ip,
+            };
+            let details = json!({"zone": zone.id, "user_id": member.user_id, "role": member.role});
+            db::audit::record(&pool, &actor, "member.remove", true, details);
+            (StatusCode::OK, Json(json!(member)))
+        }
+        Ok(None) => (
+            StatusCode::CONFLICT,
+            Json(json!({"error": "A zone must have at least one owner"})),
+        ),
+        Err(Error::RowNotFound) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Member not found"})),
+        ),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": err.to_string()})),
+        ),
+    }
+}
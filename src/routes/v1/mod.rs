@@ -1,7 +1,12 @@
+pub mod audit;
+pub mod delegations;
 pub mod features;
+pub mod history;
+pub mod members;
 pub mod metrics;
 pub mod records;
+pub mod scheduled;
 pub mod users;
 pub mod zones;
 
-mod requests;
+pub(crate) mod requests;
@@ -1,226 +1,990 @@
 use crate::db;
-use crate::extractors::{Json, Jwt};
-use crate::routes::v1::{requests, zones};
-use axum::extract::Path;
-use axum::http::StatusCode;
-use axum::response::IntoResponse;
+use crate::db::models::{Actor, Record, Role, Zone};
+use crate::db::records::RecordData;
+use crate::dns::rdata::validate_record;
+use crate::dns::rules::{self, Candidate};
+use crate::dns::{idn, names};
+use crate::extractors::{ClientIp, IfMatch, Json, Jwt};
+use crate::routes::v1::{members, requests, zones};
+use axum::extract::{Path, Query};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::Extension;
-use serde_json::json;
-use sqlx::{Pool, Postgres};
-use std::net::{Ipv4Addr, Ipv6Addr};
-use std::str::FromStr;
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::{Error, Pool, Postgres};
+use std::collections::BTreeSet;
 use std::sync::Arc;
-use trust_dns_proto::rr::{Name, RecordType};
 use uuid::Uuid;
 
+/// Largest page `get_records` will return when paging
+const MAX_PAGE_SIZE: usize = 1000;
+const MAX_COMMENT_LENGTH: usize = 1024;
+const MAX_TAG_LENGTH: usize = 64;
+const MAX_TAGS: usize = 32;
+
+#[derive(Deserialize)]
+pub struct RecordQuery {
+    #[serde(rename = "type")]
+    record_type: Option<String>,
+    name: Option<String>,
+    /// How `name` is matched: `exact` (the default), `prefix` or `contains`.
+    /// Names are stored as punycode, so Unicode in a `prefix` or `contains`
+    /// match is converted and only matches whole labels.
+    name_match: Option<String>,
+    /// Case-insensitive substring of the record content
+    content: Option<String>,
+    /// Comma-separated tags, all of which a record must have
+    tags: Option<String>,
+    /// Case-insensitive substring of the record comment
+    comment: Option<String>,
+    enabled: Option<bool>,
+    /// `name` (the default), `type` or `modified_at`, prefixed with `-` to sort
+    /// in descending order
+    sort: Option<String>,
+    limit: Option<usize>,
+    cursor: Option<String>,
+}
+
+/// Lists the records in a zone. Wildcard records are listed like any other, but
+/// an explicit name always takes precedence over a wildcard that would otherwise
+/// match it, see [`names::is_wildcard`].
+///
+/// Records can be filtered and sorted with [`RecordQuery`]. The body is always a
+/// plain array; the number of matching records is returned in `X-Total-Count`.
+/// Every matching record is returned unless `limit` or `cursor` is given, in
+/// which case pages hold at most 1000 records and `X-Next-Cursor` holds the
+/// cursor for the next page, if there is one.
 pub async fn get_records(
     Path(id): Path<String>,
+    Query(query): Query<RecordQuery>,
     Jwt(user): Jwt,
     Extension(pool): Extension<Arc<Pool<Postgres>>>,
-) -> impl IntoResponse {
-    let domain = zones::ensure_trailing_dot(&id);
+) -> Response {
+    let domain = zones::normalize_zone_id(&id);
 
     let zone = db::zones::get_zone(&pool, &domain).await;
     if zone.is_err() {
         return (
             StatusCode::NOT_FOUND,
             Json(json!({ "error": format!("Zone {domain} not found") })),
-        );
+        )
+            .into_response();
     }
     let zone = zone.unwrap();
 
-    if zone.owner_uuid != user.sub {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(json!({ "error": "You do have permissions to access this zone" })),
-        );
+    if let Err(e) = members::authorize(&pool, &zone, user.sub, Role::Viewer).await {
+        return e.into_response();
     }
 
-    let records: Vec<db::models::Record> = db::records::get_records(&pool, &zone.id)
-        .await
-        .unwrap()
-        .into_iter()
-        .filter(|r| r.record_type != "SOA" && r.record_type != "NS")
+    let filter = match record_filter(&zone, &query) {
+        Ok(filter) => filter,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response(),
+    };
+    let total = db::records::count_records(&pool, &zone.id, &filter).await;
+    let page = db::records::list_records(&pool, &zone.id, &filter).await;
+    let (total, (records, more)) = match (total, page) {
+        (Ok(total), Ok(page)) => (total, page),
+        (Err(err), _) | (_, Err(err)) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": err.to_string()})),
+            )
+                .into_response()
+        }
+    };
+
+    let next_cursor = match more {
+        true => records.last().map(|(key, r)| encode_cursor(key, &r.id)),
+        false => None,
+    };
+    let records: Vec<Value> = records
+        .iter()
+        .map(|(_, record)| idn::with_unicode(record, "name"))
         .collect();
+    let mut response = (
+        StatusCode::OK,
+        [
+            (header::ETAG, zone.etag()),
+            (
+                header::HeaderName::from_static("x-total-count"),
+                total.to_string(),
+            ),
+        ],
+        Json(json!(records)),
+    )
+        .into_response();
+    if let Some(cursor) = next_cursor {
+        response.headers_mut().insert(
+            header::HeaderName::from_static("x-next-cursor"),
+            header::HeaderValue::from_str(&cursor).unwrap(),
+        );
+    }
+    response
+}
+
+/// Turns the listing query into a filter for the database. Pages are keyed on
+/// the sort value and id of the last record rather than an offset, so records
+/// being added or removed between requests doesn't skip or repeat records.
+fn record_filter(zone: &Zone, query: &RecordQuery) -> Result<db::records::RecordFilter, String> {
+    let (name, name_prefix, name_contains) = match (&query.name, query.name_match.as_deref()) {
+        (None, _) => (None, None, None),
+        (Some(name), None | Some("exact")) => {
+            (Some(names::qualify_owner(&zone.id, name)?), None, None)
+        }
+        (Some(name), Some("prefix")) => (None, Some(idn::to_ascii(name.trim())?), None),
+        (Some(name), Some("contains")) => (None, None, Some(idn::to_ascii(name.trim())?)),
+        (Some(_), Some(other)) => return Err(format!("Unknown name_match `{other}`")),
+    };
+    let tags = match &query.tags {
+        Some(tags) => normalize_tags(tags.split(','))?,
+        None => Vec::new(),
+    };
+
+    let sort = query.sort.as_deref().unwrap_or("name");
+    let (field, descending) = match sort.strip_prefix('-') {
+        Some(field) => (field, true),
+        None => (sort, false),
+    };
+    if !matches!(field, "name" | "type" | "modified_at") {
+        return Err(format!("Unknown sort field `{field}`"));
+    }
+
+    let after = match &query.cursor {
+        Some(cursor) => Some(decode_cursor(cursor)?),
+        None => None,
+    };
+    // Only page when asked to, so a plain listing is never cut short
+    let limit = match (query.limit, &query.cursor) {
+        (None, None) => None,
+        (limit, _) => Some(limit.unwrap_or(MAX_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)),
+    };
 
-    (StatusCode::OK, Json(json!(records)))
+    Ok(db::records::RecordFilter {
+        record_type: query.record_type.clone(),
+        name,
+        name_prefix,
+        name_contains,
+        content: query.content.clone(),
+        tags,
+        comment: query.comment.clone(),
+        enabled: query.enabled,
+        sort: field.to_owned(),
+        descending,
+        after,
+        limit,
+    })
 }
 
-pub async fn create_record(
-    Path(zone_id): Path<String>,
+fn encode_cursor(key: &str, id: &Uuid) -> String {
+    hex::encode(json!([key, id]).to_string())
+}
+
+fn decode_cursor(cursor: &str) -> Result<(String, Uuid), String> {
+    let invalid = || String::from("Invalid cursor");
+    let bytes = hex::decode(cursor).map_err(|_| invalid())?;
+    let (key, id): (String, Uuid) = serde_json::from_slice(&bytes).map_err(|_| invalid())?;
+    Ok((key, id))
+}
+
+pub async fn get_record(
+    Path((zone_id, record_id)): Path<(String, String)>,
     Jwt(user): Jwt,
-    Json(data): Json<requests::Record>,
     Extension(pool): Extension<Arc<Pool<Postgres>>>,
-) -> impl IntoResponse {
-    let zone = db::zones::get_zone(&pool, &zone_id).await;
+) -> Response {
+    let zone = db::zones::get_zone(&pool, &zones::normalize_zone_id(&zone_id)).await;
 
     if zone.is_err() {
         return (
             StatusCode::NOT_FOUND,
             Json(json!({"error": "Zone not found"})),
-        );
+        )
+            .into_response();
     }
     let zone = zone.unwrap();
 
-    if zone.owner_uuid != user.sub {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(json!({"error": "You do not have permissions to access this zone"})),
-        );
+    if let Err(e) = members::authorize(&pool, &zone, user.sub, Role::Viewer).await {
+        return e.into_response();
     }
 
-    if !data.name.ends_with(&zone.id) {
+    // Managed records are hidden from listings, so they're not found here either
+    let record = match load_record(&pool, &zone, &record_id).await {
+        Ok(record) if !rules::is_managed(&zone.id, &record) => record,
+        Ok(_) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Record not found"})),
+            )
+                .into_response()
+        }
+        Err(e) => return e.into_response(),
+    };
+
+    (
+        StatusCode::OK,
+        [(header::ETAG, record.etag())],
+        Json(idn::with_unicode(&record, "name")),
+    )
+        .into_response()
+}
+
+pub async fn create_record(
+    Path(zone_id): Path<String>,
+    Jwt(user): Jwt,
+    ClientIp(client_ip): ClientIp,
+    Json(data): Json<requests::Record>,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> Response {
+    let zone = db::zones::get_zone(&pool, &zones::normalize_zone_id(&zone_id)).await;
+
+    if zone.is_err() {
         return (
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": "Record name must be fully qualified"})),
-        );
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Zone not found"})),
+        )
+            .into_response();
     }
+    let zone = zone.unwrap();
 
-    if let Err(e) = validate_record(&data.record_type, &data.content) {
-        return (StatusCode::BAD_REQUEST, Json(json!({ "error": e })));
+    if let Err(e) = members::authorize(&pool, &zone, user.sub, Role::Editor).await {
+        return e.into_response();
     }
 
-    let record = db::records::create_record(
-        &pool,
-        &zone.id,
-        &data.name,
-        &data.record_type,
-        &data.content,
-        data.ttl,
-    )
+    let actor = Actor {
+        user_id: Some(user.sub),
+        client_ip,
+    };
+    let result = db::records::write_zone(&pool, &actor, &zone.id, |zone, records| {
+        let valid = validate_write(zone, &records, &data)
+            .map_err(|e| (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))))?;
+        Ok(vec![db::records::Change::Create(valid)])
+    })
     .await;
-    if record.is_err() {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": record.unwrap_err().to_string()})),
-        );
-    }
-    let record = record.unwrap();
-
-    (StatusCode::OK, Json(json!(record)))
+    written_record(result, None)
 }
 
+/// Replaces a record. Leaving out the TTL keeps the record's current one rather
+/// than resetting it to the zone's default. Records with the same name and type
+/// must share a TTL, so changing the TTL of a record that has siblings is
+/// refused; set the whole RRset with [`set_rrset`] or [`apply_changes`] instead.
 pub async fn update_record(
     Path((zone_id, record_id)): Path<(String, String)>,
     Jwt(user): Jwt,
+    ClientIp(client_ip): ClientIp,
+    if_match: IfMatch,
     Json(data): Json<requests::Record>,
     Extension(pool): Extension<Arc<Pool<Postgres>>>,
-) -> impl IntoResponse {
-    let zone = db::zones::get_zone(&pool, &zone_id).await;
+) -> Response {
+    let zone = db::zones::get_zone(&pool, &zones::normalize_zone_id(&zone_id)).await;
 
     if zone.is_err() {
         return (
             StatusCode::NOT_FOUND,
             Json(json!({"error": "Zone not found"})),
-        );
+        )
+            .into_response();
     }
     let zone = zone.unwrap();
 
-    if zone.owner_uuid != user.sub {
+    if let Err(e) = members::authorize(&pool, &zone, user.sub, Role::Editor).await {
+        return e.into_response();
+    }
+
+    let actor = Actor {
+        user_id: Some(user.sub),
+        client_ip,
+    };
+    replace_record(&pool, &actor, &zone, &record_id, &if_match, |record| {
+        requests::Record {
+            ttl: data.ttl.or(Some(record.ttl as u32)),
+            ..data
+        }
+    })
+    .await
+}
+
+/// Updates only the fields present in the request, leaving the rest of the
+/// record as it is. As with [`update_record`], the TTL of a record that has
+/// siblings in its RRset can only be changed through [`set_rrset`] or
+/// [`apply_changes`].
+pub async fn patch_record(
+    Path((zone_id, record_id)): Path<(String, String)>,
+    Jwt(user): Jwt,
+    ClientIp(client_ip): ClientIp,
+    if_match: IfMatch,
+    Json(data): Json<requests::RecordPatch>,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> Response {
+    let zone = db::zones::get_zone(&pool, &zones::normalize_zone_id(&zone_id)).await;
+
+    if zone.is_err() {
         return (
-            StatusCode::FORBIDDEN,
-            Json(json!({"error": "You do not have permissions to access this zone"})),
-        );
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Zone not found"})),
+        )
+            .into_response();
+    }
+    let zone = zone.unwrap();
+
+    if let Err(e) = members::authorize(&pool, &zone, user.sub, Role::Editor).await {
+        return e.into_response();
     }
 
-    // TODO: Check to make sure record is within zone
+    let actor = Actor {
+        user_id: Some(user.sub),
+        client_ip,
+    };
+    replace_record(&pool, &actor, &zone, &record_id, &if_match, |record| {
+        merge_patch(record, data)
+    })
+    .await
+}
 
-    if !data.name.ends_with(&zone.id) {
+pub async fn delete_record(
+    Path((zone_id, record_id)): Path<(String, String)>,
+    Jwt(user): Jwt,
+    ClientIp(client_ip): ClientIp,
+    if_match: IfMatch,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> Response {
+    let zone = db::zones::get_zone(&pool, &zones::normalize_zone_id(&zone_id)).await;
+
+    if zone.is_err() {
         return (
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": "Record name must be fully qualified"})),
-        );
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Zone not found"})),
+        )
+            .into_response();
     }
+    let zone = zone.unwrap();
 
-    if let Err(e) = validate_record(&data.record_type, &data.content) {
-        return (StatusCode::BAD_REQUEST, Json(json!({ "error": e })));
+    if let Err(e) = members::authorize(&pool, &zone, user.sub, Role::Editor).await {
+        return e.into_response();
     }
 
-    let record = db::records::update_record(
-        &pool,
-        &zone_id,
-        &Uuid::parse_str(&record_id).unwrap(),
-        &data.name,
-        &data.record_type,
-        &data.content,
-        data.ttl,
-    )
+    let actor = Actor {
+        user_id: Some(user.sub),
+        client_ip,
+    };
+    let result = db::records::write_zone(&pool, &actor, &zone.id, |zone, records| {
+        let index = locate(zone, &records, &record_id).map_err(error)?;
+        if_match.check(&records[index].etag())?;
+        Ok(vec![db::records::Change::Delete {
+            id: records[index].id,
+        }])
+    })
     .await;
-    if record.is_err() {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": record.unwrap_err().to_string()})),
-        );
+    if let Err(e) = written(result) {
+        return e.into_response();
     }
-    let record = record.unwrap();
 
-    (StatusCode::OK, Json(json!(record)))
+    (
+        StatusCode::OK,
+        Json(json!({
+            "message": format!("Record {record_id} deleted")
+        })),
+    )
+        .into_response()
 }
 
-pub async fn delete_record(
+/// Puts a disabled record back in the zone
+pub async fn enable_record(
+    Path((zone_id, record_id)): Path<(String, String)>,
+    Jwt(user): Jwt,
+    ClientIp(client_ip): ClientIp,
+    if_match: IfMatch,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> Response {
+    let actor = Actor {
+        user_id: Some(user.sub),
+        client_ip,
+    };
+    set_enabled(
+        &pool, &actor, user.sub, &zone_id, &record_id, &if_match, true,
+    )
+    .await
+}
+
+/// Takes a record out of the zone without deleting it, so it keeps its history
+/// and can be put back with [`enable_record`]
+pub async fn disable_record(
     Path((zone_id, record_id)): Path<(String, String)>,
     Jwt(user): Jwt,
+    ClientIp(client_ip): ClientIp,
+    if_match: IfMatch,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> Response {
+    let actor = Actor {
+        user_id: Some(user.sub),
+        client_ip,
+    };
+    set_enabled(
+        &pool, &actor, user.sub, &zone_id, &record_id, &if_match, false,
+    )
+    .await
+}
+
+async fn set_enabled(
+    pool: &Pool<Postgres>,
+    actor: &Actor,
+    user_id: Uuid,
+    zone_id: &str,
+    record_id: &str,
+    if_match: &IfMatch,
+    enabled: bool,
+) -> Response {
+    let zone = match db::zones::get_zone(pool, &zones::normalize_zone_id(zone_id)).await {
+        Ok(zone) => zone,
+        Err(_) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Zone not found"})),
+            )
+                .into_response()
+        }
+    };
+
+    if let Err(e) = members::authorize(pool, &zone, user_id, Role::Editor).await {
+        return e.into_response();
+    }
+
+    let mut unchanged = None;
+    let result = db::records::write_zone(pool, actor, &zone.id, |zone, mut records| {
+        let record = records.remove(locate(zone, &records, record_id).map_err(error)?);
+        if_match.check(&record.etag())?;
+        // Nothing to write (or bump the serial for) if it's already in that state
+        if record.enabled == enabled {
+            unchanged = Some(record);
+            return Ok(Vec::new());
+        }
+        let data = RecordData {
+            enabled,
+            ..RecordData::from(&record)
+        };
+        Ok(vec![db::records::Change::Update {
+            id: record.id,
+            data,
+        }])
+    })
+    .await;
+    written_record(result, unchanged)
+}
+
+/// Validates a batch of creates, updates and deletes against the zone and
+/// applies them atomically. The zone's rules are checked against the result of
+/// the whole batch, so e.g. a CNAME can replace the A records at a name or
+/// every record in an RRset can get a new TTL in one go.
+pub async fn apply_changes(
+    Path(zone_id): Path<String>,
+    Jwt(user): Jwt,
+    ClientIp(client_ip): ClientIp,
+    if_match: IfMatch,
+    Json(changes): Json<Vec<requests::Change>>,
     Extension(pool): Extension<Arc<Pool<Postgres>>>,
-) -> impl IntoResponse {
-    let zone = db::zones::get_zone(&pool, &zone_id).await;
+) -> Response {
+    let zone = db::zones::get_zone(&pool, &zones::normalize_zone_id(&zone_id)).await;
 
     if zone.is_err() {
         return (
             StatusCode::NOT_FOUND,
             Json(json!({"error": "Zone not found"})),
-        );
+        )
+            .into_response();
     }
     let zone = zone.unwrap();
 
-    if zone.owner_uuid != user.sub {
+    if let Err(e) = members::authorize(&pool, &zone, user.sub, Role::Editor).await {
+        return e.into_response();
+    }
+    if changes.is_empty() {
         return (
-            StatusCode::FORBIDDEN,
-            Json(json!({"error": "You do not have permissions to access this zone"})),
-        );
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "No changes given"})),
+        )
+            .into_response();
     }
 
-    // TODO: Make sure record exists
-    // TODO: Check to make sure record is within zone
+    let actor = Actor {
+        user_id: Some(user.sub),
+        client_ip,
+    };
+    let result = db::records::write_zone(&pool, &actor, &zone.id, |zone, records| {
+        if_match.check(&zone.etag())?;
+        plan_changes(zone, records, changes)
+            .map_err(|(index, status, e)| (status, Json(json!({ "error": e, "index": index }))))
+    })
+    .await;
+    let results = match written(result) {
+        Ok(results) => results,
+        Err(e) => return e.into_response(),
+    };
+
+    let results: Vec<Value> = results
+        .into_iter()
+        .map(|(change, record)| {
+            let record = record.map(|record| idn::with_unicode(&record, "name"));
+            match change {
+                db::records::Change::Create(_) => json!({"action": "create", "record": record}),
+                db::records::Change::Update { .. } => json!({"action": "update", "record": record}),
+                db::records::Change::Delete { id } => json!({"action": "delete", "id": id}),
+            }
+        })
+        .collect();
+    (StatusCode::OK, Json(json!(results))).into_response()
+}
 
-    let result =
-        db::records::delete_record(&pool, &zone_id, &Uuid::parse_str(&record_id).unwrap()).await;
-    if result.is_err() {
+/// Makes the records for a name and type exactly match the submitted list,
+/// creating, updating and deleting rows as needed. Submitting the same set again
+/// changes nothing, not even the serial.
+pub async fn set_rrset(
+    Path(zone_id): Path<String>,
+    Jwt(user): Jwt,
+    ClientIp(client_ip): ClientIp,
+    Json(data): Json<requests::RRset>,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> Response {
+    let zone = db::zones::get_zone(&pool, &zones::normalize_zone_id(&zone_id)).await;
+
+    if zone.is_err() {
         return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Zone not found"})),
+        )
+            .into_response();
+    }
+    let zone = zone.unwrap();
+
+    if let Err(e) = members::authorize(&pool, &zone, user.sub, Role::Editor).await {
+        return e.into_response();
+    }
+
+    let name = match names::qualify_owner(&zone.id, &data.name) {
+        Ok(name) => name,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response(),
+    };
+    let actor = Actor {
+        user_id: Some(user.sub),
+        client_ip,
+    };
+    let mut unchanged = Vec::new();
+    let result = db::records::write_zone(&pool, &actor, &zone.id, |zone, records| {
+        plan_rrset(zone, records, &name, &data, &mut unchanged)
+    })
+    .await;
+    match written(result) {
+        Ok(results) => unchanged.extend(results.into_iter().filter_map(|(_, record)| record)),
+        Err(e) => return e.into_response(),
+    }
+
+    let records: Vec<Value> = unchanged
+        .iter()
+        .map(|record| idn::with_unicode(record, "name"))
+        .collect();
+    (StatusCode::OK, Json(json!(records))).into_response()
+}
+
+/// Works out the writes that make the records for `name` and `data.record_type`
+/// match `data`, adding the records that are left as they are to `unchanged`
+fn plan_rrset(
+    zone: &Zone,
+    records: Vec<Record>,
+    name: &str,
+    data: &requests::RRset,
+    unchanged: &mut Vec<Record>,
+) -> Result<Vec<db::records::Change>, (StatusCode, Json<Value>)> {
+    let (current, mut others): (Vec<Record>, Vec<Record>) = records.into_iter().partition(|r| {
+        names::same_name(&r.name, name)
+            && r.record_type == data.record_type
+            && !rules::is_managed(&zone.id, r)
+    });
+
+    // Validate the RRset as it will end up rather than one change at a time, so
+    // changing the TTL of every record doesn't trip the one TTL per RRset rule
+    let mut wanted: Vec<RecordData> = Vec::new();
+    for content in &data.contents {
+        let record = requests::Record {
+            name: name.to_owned(),
+            record_type: data.record_type.clone(),
+            content: content.clone(),
+            ttl: data.ttl,
+            expires_at: data.expires_at,
+            comment: data.comment.clone(),
+            tags: data.tags.clone(),
+        };
+        let canonical = match validate_record(&record.record_type, &record.content) {
+            Ok(canonical) => canonical,
+            Err(e) => return Err((StatusCode::BAD_REQUEST, Json(json!({ "error": e })))),
+        };
+        if wanted.iter().any(|w| w.content == canonical) {
+            continue;
+        }
+        let valid = match validate_write(zone, &others, &record) {
+            Ok(valid) => valid,
+            Err(e) => return Err((StatusCode::BAD_REQUEST, Json(json!({ "error": e })))),
+        };
+        others.push(planned_record(zone, &valid));
+        wanted.push(valid);
+    }
+
+    let mut changes = Vec::new();
+    for record in current {
+        match wanted.iter().position(|w| w.content == record.content) {
+            Some(index) => {
+                let valid = wanted.remove(index);
+                if valid.ttl == record.ttl
+                    && valid.name == record.name
+                    && valid.expires_at == record.expires_at
+                    && valid.comment == record.comment
+                    && valid.tags == record.tags
+                {
+                    unchanged.push(record);
+                } else {
+                    changes.push(db::records::Change::Update {
+                        id: record.id,
+                        data: RecordData {
+                            enabled: record.enabled,
+                            ..valid
+                        },
+                    });
+                }
+            }
+            None => changes.push(db::records::Change::Delete { id: record.id }),
+        }
+    }
+    changes.extend(wanted.into_iter().map(db::records::Change::Create));
+
+    Ok(changes)
+}
+
+/// Checks a batch of changes against the zone's `records`, returning the
+/// writes to make. Errors carry the index of the change that failed.
+pub(crate) fn plan_changes(
+    zone: &Zone,
+    mut records: Vec<Record>,
+    changes: Vec<requests::Change>,
+) -> Result<Vec<db::records::Change>, (usize, StatusCode, String)> {
+    let mut planned = Vec::with_capacity(changes.len());
+    // Each record left written by the batch, with the last change that touched it
+    let mut written: Vec<(usize, Uuid)> = Vec::new();
+    for (index, change) in changes.into_iter().enumerate() {
+        let (change, id) =
+            plan_change(zone, &mut records, change).map_err(|(status, e)| (index, status, e))?;
+        written.retain(|(_, other)| *other != id);
+        if !matches!(change, db::records::Change::Delete { .. }) {
+            written.push((index, id));
+        }
+        planned.push(change);
+    }
+
+    // The rules are checked against the zone as it ends up rather than after
+    // each change, so e.g. a batch can change the TTL of every record in an RRset
+    for (index, id) in written {
+        if let Some(position) = records.iter().position(|r| r.id == id) {
+            let last = records.len() - 1;
+            records.swap(position, last);
+            if let Some((record, others)) = records.split_last() {
+                let candidate = Candidate {
+                    name: &record.name,
+                    record_type: &record.record_type,
+                    content: &record.content,
+                    ttl: record.ttl,
+                };
+                rules::check_record(&zone.id, others, &candidate)
+                    .map_err(|e| (index, StatusCode::BAD_REQUEST, e))?;
+            }
+        }
+    }
+    Ok(planned)
+}
+
+/// Normalizes one change of a batch and applies it to `records`, the zone as it
+/// will look after the previous changes. Returns the write to make and the id
+/// of the record it affects.
+fn plan_change(
+    zone: &Zone,
+    records: &mut Vec<Record>,
+    change: requests::Change,
+) -> Result<(db::records::Change, Uuid), (StatusCode, String)> {
+    match change {
+        requests::Change::Create(data) => {
+            let valid = normalize_write(zone, &data).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+            let record = planned_record(zone, &valid);
+            let id = record.id;
+            records.push(record);
+            Ok((db::records::Change::Create(valid), id))
+        }
+        requests::Change::Update { id, patch } => {
+            let index = locate(zone, records, &id)?;
+            let record = &mut records[index];
+            let data = merge_patch(record, patch);
+            let valid = normalize_write(zone, &data).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+            let valid = RecordData {
+                enabled: record.enabled,
+                ..valid
+            };
+            record.name = valid.name.clone();
+            record.record_type = valid.record_type.clone();
+            record.content = valid.content.clone();
+            record.ttl = valid.ttl;
+            record.expires_at = valid.expires_at;
+            record.comment = valid.comment.clone();
+            record.tags = valid.tags.clone();
+            let id = record.id;
+            Ok((db::records::Change::Update { id, data: valid }, id))
+        }
+        requests::Change::Delete { id } => {
+            let record = records.remove(locate(zone, records, &id)?);
+            Ok((db::records::Change::Delete { id: record.id }, record.id))
+        }
+    }
+}
+
+/// Finds a record the user is allowed to modify in `records`, returning its index
+fn locate(zone: &Zone, records: &[Record], record_id: &str) -> Result<usize, (StatusCode, String)> {
+    let id = Uuid::parse_str(record_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, String::from("Invalid record id")))?;
+    let index = records
+        .iter()
+        .position(|r| r.id == id)
+        .ok_or((StatusCode::NOT_FOUND, String::from("Record not found")))?;
+    if rules::is_managed(&zone.id, &records[index]) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            String::from("This record is managed by HOSTSdotTXT"),
+        ));
+    }
+    Ok(index)
+}
+
+fn error((status, e): (StatusCode, String)) -> (StatusCode, Json<Value>) {
+    (status, Json(json!({ "error": e })))
+}
+
+/// Stand-in for a record that hasn't been written yet, so later checks in the
+/// same request can see it
+fn planned_record(zone: &Zone, valid: &RecordData) -> Record {
+    let now = Utc::now();
+    Record {
+        id: Uuid::new_v4(),
+        zone_id: zone.id.clone(),
+        name: valid.name.clone(),
+        record_type: valid.record_type.clone(),
+        content: valid.content.clone(),
+        ttl: valid.ttl,
+        created_at: now,
+        modified_at: now,
+        alias_id: None,
+        expires_at: valid.expires_at,
+        comment: valid.comment.clone(),
+        tags: valid.tags.clone(),
+        enabled: valid.enabled,
+    }
+}
+
+/// Replaces a record with the one `build` makes from its current version
+async fn replace_record(
+    pool: &Pool<Postgres>,
+    actor: &Actor,
+    zone: &Zone,
+    record_id: &str,
+    if_match: &IfMatch,
+    build: impl FnOnce(&Record) -> requests::Record,
+) -> Response {
+    let result = db::records::write_zone(pool, actor, &zone.id, |zone, mut records| {
+        let record = records.remove(locate(zone, &records, record_id).map_err(error)?);
+        if_match.check(&record.etag())?;
+        let data = build(&record);
+        let valid = validate_write(zone, &records, &data)
+            .map_err(|e| (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))))?;
+        Ok(vec![db::records::Change::Update {
+            id: record.id,
+            data: RecordData {
+                enabled: record.enabled,
+                ..valid
+            },
+        }])
+    })
+    .await;
+    written_record(result, None)
+}
+
+type Written = Vec<(db::records::Change, Option<Record>)>;
+
+/// Unpacks the result of [`db::records::write_zone`], turning failures into
+/// responses
+fn written(
+    result: Result<Result<Written, (StatusCode, Json<Value>)>, Error>,
+) -> Result<Written, (StatusCode, Json<Value>)> {
+    match result {
+        Ok(Ok(results)) => Ok(results),
+        Ok(Err(e)) => Err(e),
+        Err(Error::RowNotFound) => Err((
+            StatusCode::CONFLICT,
+            Json(
+                json!({"error": "The zone changed while applying the changes, nothing was applied"}),
+            ),
+        )),
+        Err(err) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": result.unwrap_err().to_string()})),
-        );
+            Json(json!({"error": err.to_string()})),
+        )),
     }
+}
 
-    (
-        StatusCode::OK,
-        Json(json!({
-            "message": format!("Record {} deleted", record_id)
-        })),
-    )
+/// Responds with the record written by a single-record change, or `unchanged`
+/// if there was nothing to write
+fn written_record(
+    result: Result<Result<Written, (StatusCode, Json<Value>)>, Error>,
+    unchanged: Option<Record>,
+) -> Response {
+    let record = match written(result) {
+        Ok(results) => results.into_iter().find_map(|(_, record)| record),
+        Err(e) => return e.into_response(),
+    };
+    match record.or(unchanged) {
+        Some(record) => (
+            StatusCode::OK,
+            [(header::ETAG, record.etag())],
+            Json(idn::with_unicode(&record, "name")),
+        )
+            .into_response(),
+        None => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "No record was written"})),
+        )
+            .into_response(),
+    }
+}
+
+/// Looks up a record by id, only finding it in the zone it belongs to
+async fn load_record(
+    pool: &Pool<Postgres>,
+    zone: &Zone,
+    record_id: &str,
+) -> Result<Record, (StatusCode, Json<Value>)> {
+    let record_id = match Uuid::parse_str(record_id) {
+        Ok(record_id) => record_id,
+        Err(_) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid record id"})),
+            ))
+        }
+    };
+    match db::records::get_record(pool, &zone.id, &record_id).await {
+        Ok(record) => Ok(record),
+        Err(Error::RowNotFound) => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Record not found"})),
+        )),
+        Err(err) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": err.to_string()})),
+        )),
+    }
+}
+
+/// Fills in the fields missing from a partial update from the current record.
+/// `expires_at` and `comment` can be cleared by sending them as `null`.
+fn merge_patch(record: &Record, patch: requests::RecordPatch) -> requests::Record {
+    requests::Record {
+        name: patch.name.unwrap_or_else(|| record.name.clone()),
+        record_type: patch
+            .record_type
+            .unwrap_or_else(|| record.record_type.clone()),
+        content: patch.content.unwrap_or_else(|| record.content.clone()),
+        ttl: Some(patch.ttl.unwrap_or(record.ttl as u32)),
+        expires_at: patch.expires_at.unwrap_or(record.expires_at),
+        comment: patch.comment.unwrap_or_else(|| record.comment.clone()),
+        tags: patch.tags.unwrap_or_else(|| record.tags.clone()),
+    }
 }
 
-fn validate_record(rtype: &str, content: &str) -> Result<(), String> {
-    match RecordType::from_str(rtype) {
-        Ok(rtype) => match rtype {
-            RecordType::A => content
-                .parse::<Ipv4Addr>()
-                .map(|_| ())
-                .map_err(|_| String::from("Invalid IPv4 address")),
-            RecordType::AAAA => content
-                .parse::<Ipv6Addr>()
-                .map(|_| ())
-                .map_err(|_| String::from("Invalid IPv6 address")),
-            RecordType::CNAME => content
-                .parse::<Name>()
-                .map(|_| ())
-                .map_err(|_| String::from("Invalid CNAME")),
-            RecordType::MX => content
-                .parse::<Name>()
-                .map(|_| ())
-                .map_err(|_| String::from("Invalid MX record")),
-            RecordType::TXT => Ok(()),
-            _ => Err(String::from("Unknown record type")),
-        },
-        _ => Err(String::from("Unknown record type")),
+/// Validates a record write against the zone, returning it in the normalized
+/// form we store. `existing` must not include the record being replaced, if any.
+/// The result is enabled; updates carry over the flag from the existing record.
+pub(crate) fn validate_write(
+    zone: &Zone,
+    existing: &[Record],
+    data: &requests::Record,
+) -> Result<RecordData, String> {
+    let valid = normalize_write(zone, data)?;
+    check_written(zone, existing, &valid)?;
+    Ok(valid)
+}
+
+/// Validates and normalizes the fields of a record on their own, without
+/// checking it against the rest of the zone
+fn normalize_write(zone: &Zone, data: &requests::Record) -> Result<RecordData, String> {
+    let name = names::qualify_owner(&zone.id, &data.name)?;
+    let content = validate_record(&data.record_type, &data.content)?;
+    let ttl = rules::validate_ttl(data.ttl.unwrap_or(zone.default_ttl as u32))?;
+    if let Some(expires_at) = data.expires_at {
+        if expires_at <= Utc::now() {
+            return Err(String::from("expires_at must be in the future"));
+        }
+    }
+    let comment = validate_comment(data.comment.as_deref())?;
+    let tags = normalize_tags(data.tags.iter().map(String::as_str))?;
+
+    Ok(RecordData {
+        name,
+        record_type: data.record_type.clone(),
+        content,
+        ttl,
+        expires_at: data.expires_at,
+        comment,
+        tags,
+        enabled: true,
+    })
+}
+
+/// Checks that a normalized record fits in with the `existing` records of the
+/// zone, which must not include the record itself
+fn check_written(zone: &Zone, existing: &[Record], valid: &RecordData) -> Result<(), String> {
+    let candidate = Candidate {
+        name: &valid.name,
+        record_type: &valid.record_type,
+        content: &valid.content,
+        ttl: valid.ttl,
+    };
+    rules::check_record(&zone.id, existing, &candidate)
+}
+
+/// Trims the comment, treating an empty one as no comment
+fn validate_comment(comment: Option<&str>) -> Result<Option<String>, String> {
+    match comment.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(comment) if comment.chars().count() > MAX_COMMENT_LENGTH => Err(format!(
+            "Comments can be at most {MAX_COMMENT_LENGTH} characters"
+        )),
+        Some(comment) => Ok(Some(comment.to_owned())),
+    }
+}
+
+/// Lowercases, sorts and deduplicates tags. Tags are made of ASCII letters,
+/// digits, `-`, `_`, `.`, `:` and `/`, so they can be listed comma-separated
+/// in queries.
+fn normalize_tags<'a>(tags: impl IntoIterator<Item = &'a str>) -> Result<Vec<String>, String> {
+    let mut normalized = BTreeSet::new();
+    for tag in tags {
+        let tag = tag.trim().to_ascii_lowercase();
+        if tag.is_empty() || tag.chars().count() > MAX_TAG_LENGTH {
+            return Err(format!(
+                "Tags must be between 1 and {MAX_TAG_LENGTH} characters"
+            ));
+        }
+        if !tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':' | '/'))
+        {
+            return Err(format!("Invalid tag `{tag}`"));
+        }
+        normalized.insert(tag);
+    }
+    if normalized.len() > MAX_TAGS {
+        return Err(format!("Records can have at most {MAX_TAGS} tags"));
     }
+    Ok(normalized.into_iter().collect())
 }
@@ -1,4 +1,6 @@
-use serde::{Deserialize, Serialize};
+use crate::db::models::Role;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Signup {
@@ -19,5 +21,111 @@ pub struct Record {
     #[serde(rename = "type")]
     pub record_type: String,
     pub content: String,
-    pub ttl: u32,
+    pub ttl: Option<u32>,
+    /// The record is deleted automatically once this time has passed
+    pub expires_at: Option<DateTime<Utc>>,
+    pub comment: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RecordPatch {
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub record_type: Option<String>,
+    pub content: Option<String>,
+    pub ttl: Option<u32>,
+    /// `None` if missing, `Some(None)` if `null`
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub expires_at: Option<Option<DateTime<Utc>>>,
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub comment: Option<Option<String>>,
+    pub tags: Option<Vec<String>>,
+}
+
+/// One operation in a batch of record changes, tagged by `action`. Updates
+/// have the same partial semantics as `PATCH`.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum Change {
+    Create(Record),
+    Update {
+        id: String,
+        #[serde(flatten)]
+        patch: RecordPatch,
+    },
+    Delete {
+        id: String,
+    },
+}
+
+/// The complete set of records for a name and type. An empty `contents`
+/// deletes the RRset.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RRset {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub record_type: String,
+    pub ttl: Option<u32>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub comment: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub contents: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ScheduledChange {
+    pub apply_at: DateTime<Utc>,
+    pub changes: Vec<Change>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Restore {
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Delegation {
+    pub prefix: String,
+    pub owner_email: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Member {
+    pub email: String,
+    pub role: Role,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Soa {
+    pub rname: Option<String>,
+    pub refresh: Option<u32>,
+    pub retry: Option<u32>,
+    pub expire: Option<u32>,
+    pub minimum: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ZoneSettings {
+    pub default_ttl: Option<u32>,
+}
+
+/// Deserializes a field that's present, even if `null`, as `Some`, so it can be
+/// told apart from a missing field
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
 }
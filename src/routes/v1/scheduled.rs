@@ -0,0 +1,161 @@
+use crate::db;
+use crate::db::models::Role;
+use crate::extractors::{Json, Jwt};
+use crate::routes::v1::{members, records, requests, zones};
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use chrono::Utc;
+use serde_json::json;
+use sqlx::types::Uuid;
+use sqlx::{Error, Pool, Postgres};
+use std::sync::Arc;
+
+/// Schedules a batch of record changes, in the same format as
+/// [`records::apply_changes`], to be applied at `apply_at` by
+/// `tasks::scheduled`. The changes are checked against the zone now so mistakes
+/// show up straight away, and again when they're applied.
+pub async fn create_scheduled_change(
+    Path(zone_id): Path<String>,
+    Jwt(user): Jwt,
+    Json(data): Json<requests::ScheduledChange>,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> impl IntoResponse {
+    let zone = db::zones::get_zone(&pool, &zones::normalize_zone_id(&zone_id)).await;
+
+    if zone.is_err() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Zone not found"})),
+        );
+    }
+    let zone = zone.unwrap();
+
+    if let Err(e) = members::authorize(&pool, &zone, user.sub, Role::Editor).await {
+        return e;
+    }
+    if data.apply_at <= Utc::now() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "`apply_at` must be in the future"})),
+        );
+    }
+    if data.changes.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "No changes given"})),
+        );
+    }
+
+    let changes = json!(data.changes);
+    let records = match db::records::get_records(&pool, &zone.id).await {
+        Ok(records) => records,
+        Err(err) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": err.to_string()})),
+            )
+        }
+    };
+    if let Err((index, status, e)) = records::plan_changes(&zone, records, data.changes) {
+        return (status, Json(json!({ "error": e, "index": index })));
+    }
+
+    let scheduled =
+        db::scheduled::create_scheduled_change(&pool, &zone.id, user.sub, &data.apply_at, &changes)
+            .await;
+    match scheduled {
+        Ok(scheduled) => (StatusCode::OK, Json(json!(scheduled))),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": err.to_string()})),
+        ),
+    }
+}
+
+pub async fn list_scheduled_changes(
+    Path(zone_id): Path<String>,
+    Jwt(user): Jwt,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> impl IntoResponse {
+    let zone = db::zones::get_zone(&pool, &zones::normalize_zone_id(&zone_id)).await;
+
+    if zone.is_err() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Zone not found"})),
+        );
+    }
+    let zone = zone.unwrap();
+
+    if let Err(e) = members::authorize(&pool, &zone, user.sub, Role::Viewer).await {
+        return e;
+    }
+
+    match db::scheduled::get_scheduled_changes(&pool, &zone.id).await {
+        Ok(scheduled) => (StatusCode::OK, Json(json!(scheduled))),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": err.to_string()})),
+        ),
+    }
+}
+
+/// Cancels a scheduled change that hasn't been applied yet
+pub async fn cancel_scheduled_change(
+    Path((zone_id, scheduled_id)): Path<(String, String)>,
+    Jwt(user): Jwt,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> impl IntoResponse {
+    let zone = db::zones::get_zone(&pool, &zones::normalize_zone_id(&zone_id)).await;
+
+    if zone.is_err() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Zone not found"})),
+        );
+    }
+    let zone = zone.unwrap();
+
+    if let Err(e) = members::authorize(&pool, &zone, user.sub, Role::Editor).await {
+        return e;
+    }
+
+    let scheduled_id = match Uuid::parse_str(&scheduled_id) {
+        Ok(id) => id,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid scheduled change id"})),
+            )
+        }
+    };
+    match db::scheduled::get_scheduled_change(&pool, &zone.id, &scheduled_id).await {
+        Ok(_) => {}
+        Err(Error::RowNotFound) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Scheduled change not found"})),
+            )
+        }
+        Err(err) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": err.to_string()})),
+            )
+        }
+    }
+
+    match db::scheduled::cancel_scheduled_change(&pool, &zone.id, &scheduled_id).await {
+        Ok(Some(scheduled)) => (StatusCode::OK, Json(json!(scheduled))),
+        Ok(None) => (
+            StatusCode::CONFLICT,
+            Json(json!({"error": "Only pending changes can be cancelled"})),
+        ),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": err.to_string()})),
+        ),
+    }
+}
@@ -1,6 +1,6 @@
 use crate::db;
-use crate::db::models::User;
-use crate::extractors::{Json, Jwt};
+use crate::db::models::{Actor, User};
+use crate::extractors::{ClientIp, Json, Jwt};
 use crate::routes::v1::requests;
 use axum::extract::Query;
 use axum::http::StatusCode;
@@ -21,6 +21,7 @@ lazy_static! {
 }
 
 pub async fn create_user(
+    ClientIp(client_ip): ClientIp,
     Json(signup): Json<requests::Signup>,
     Extension(pool): Extension<Arc<Pool<Postgres>>>,
 ) -> impl IntoResponse {
@@ -39,7 +40,14 @@ pub async fn create_user(
     }
     let user = db::users::create_user(&pool, &signup.email, &signup.password).await;
     match user {
-        Ok(user) => (StatusCode::OK, Json(json!({ "token": issue_jwt(user) }))),
+        Ok(user) => {
+            let actor = Actor {
+                user_id: Some(user.id),
+                client_ip,
+            };
+            db::audit::record(&pool, &actor, "signup", true, json!({"email": user.email}));
+            (StatusCode::OK, Json(json!({ "token": issue_jwt(user) })))
+        }
         Err(err) => match err {
             Error::Database(e) if e.code().unwrap_or(std::borrow::Cow::Borrowed("")) == "23505" => {
                 (
@@ -106,6 +114,7 @@ pub async fn needs_totp(
 }
 
 pub async fn login(
+    ClientIp(client_ip): ClientIp,
     Json(login_req): Json<requests::Login>,
     Extension(pool): Extension<Arc<Pool<Postgres>>>,
 ) -> impl IntoResponse {
@@ -114,6 +123,12 @@ pub async fn login(
         Ok(user) => user,
         Err(err) => match err {
             Error::RowNotFound => {
+                let actor = Actor {
+                    user_id: None,
+                    client_ip,
+                };
+                let details = json!({"email": login_req.email, "reason": "unknown_email"});
+                db::audit::record(&pool, &actor, "login", false, details);
                 return (
                     StatusCode::UNAUTHORIZED,
                     Json(json!({"error": "Invalid email or password"})),
@@ -128,19 +143,28 @@ pub async fn login(
         },
     };
 
+    let actor = Actor {
+        user_id: Some(user.id),
+        client_ip,
+    };
     if !bcrypt::verify(&login_req.password, &user.password).unwrap_or(false) {
+        let details = json!({"email": user.email, "reason": "bad_password"});
+        db::audit::record(&pool, &actor, "login", false, details);
         return (
             StatusCode::UNAUTHORIZED,
             Json(json!({"error": "Invalid email or password"})),
         );
     }
     if !user.enabled {
+        let details = json!({"email": user.email, "reason": "disabled"});
+        db::audit::record(&pool, &actor, "login", false, details);
         return (
             StatusCode::UNAUTHORIZED,
             Json(json!({"error": "Invalid email or password"})),
         );
     }
 
+    db::audit::record(&pool, &actor, "login", true, json!({"email": user.email}));
     let token = issue_jwt(user);
     (StatusCode::OK, Json(json!({ "token": token })))
 }
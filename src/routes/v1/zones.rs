@@ -1,15 +1,25 @@
-use crate::db;
+use crate::db::models::{Actor, Role};
+use crate::db::records::RecordData;
+use crate::dns::soa::{self, Soa};
+use crate::dns::{idn, names, rules};
+use crate::extractors::ClientIp;
+use crate::extractors::IfMatch;
 use crate::extractors::Json;
 use crate::extractors::Jwt;
+use crate::routes::v1::{members, requests};
+use crate::{db, dns};
 use axum::extract::Path;
 use axum::extract::Query;
-use axum::http::StatusCode;
-use axum::response::IntoResponse;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::Extension;
+use ipnet::IpNet;
 use lazy_static::lazy_static;
 use serde::Deserialize;
-use serde_json::json;
+use serde_json::{json, Value};
+use sqlx::types::Uuid;
 use sqlx::{Error, Pool, Postgres};
+use std::net::IpAddr;
 use std::sync::Arc;
 use whois_rust::{WhoIs, WhoIsLookupOptions};
 
@@ -26,7 +36,13 @@ pub async fn list_zones(
 ) -> impl IntoResponse {
     let zones = db::zones::get_zones(&pool, user.sub).await;
     match zones {
-        Ok(zones) => (StatusCode::OK, Json(json!(zones))),
+        Ok(zones) => (
+            StatusCode::OK,
+            Json(json!(zones
+                .iter()
+                .map(|zone| idn::with_unicode(zone, "id"))
+                .collect::<Vec<Value>>())),
+        ),
         Err(err) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({"error": err.to_string()})),
@@ -61,13 +77,72 @@ pub async fn get_root_domain(
     }
 }
 
+#[derive(Deserialize)]
+pub struct ReverseNameQuery {
+    ip: String,
+}
+pub async fn get_reverse_name(
+    Query(query): Query<ReverseNameQuery>,
+    Jwt(user): Jwt,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> impl IntoResponse {
+    let ip = match query.ip.parse::<IpAddr>() {
+        Ok(ip) => ip,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid IP address"})),
+            )
+        }
+    };
+    let name = dns::reverse::reverse_name(ip);
+
+    match db::zones::get_zones(&pool, user.sub).await {
+        Ok(zones) => {
+            let longest_zone = zones
+                .iter()
+                .filter(|zone| name.ends_with(&format!(".{}", zone.id)))
+                .max_by(|x, y| x.id.len().cmp(&y.id.len()))
+                .map(|zone| zone.id.clone());
+            (
+                StatusCode::OK,
+                Json(json!({"name": name, "zone": longest_zone})),
+            )
+        }
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": err.to_string()})),
+        ),
+    }
+}
+
 pub async fn create_zone(
     Path(zone_id): Path<String>,
     Jwt(user): Jwt,
+    ClientIp(client_ip): ClientIp,
     Extension(pool): Extension<Arc<Pool<Postgres>>>,
     Extension(whois_client): Extension<WhoIs>,
 ) -> impl IntoResponse {
-    let domain = ensure_trailing_dot(&zone_id);
+    let domain = match idn::to_ascii(&ensure_trailing_dot(&zone_id)) {
+        Ok(domain) => domain,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "error": "Invalid domain",
+                    "message": e
+                })),
+            )
+        }
+    };
+    if dns::reverse::is_reverse_zone(&domain) {
+        let actor = Actor {
+            user_id: Some(user.sub),
+            client_ip,
+        };
+        return create_reverse_zone(&pool, &actor, &domain, user.sub).await;
+    }
+
     let domain = addr::parse_domain_name(&domain).unwrap();
     if !domain.has_known_suffix() {
         return (
@@ -123,7 +198,68 @@ pub async fn create_zone(
         );
     }
 
-    let zone = db::zones::create_zone(&pool, &zone_id, user.sub).await;
+    let actor = Actor {
+        user_id: Some(user.sub),
+        client_ip,
+    };
+    insert_zone(&pool, &actor, &zone_id, user.sub).await
+}
+
+/// Reverse zones can't be checked against WhoIs, so instead the zone must fall
+/// within a prefix an admin has delegated to the user.
+async fn create_reverse_zone(
+    pool: &Pool<Postgres>,
+    actor: &Actor,
+    zone_id: &str,
+    owner_uuid: Uuid,
+) -> (StatusCode, Json<Value>) {
+    let prefix = match dns::reverse::zone_prefix(zone_id) {
+        Ok(prefix) => prefix,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "error": "Invalid domain",
+                    "message": e
+                })),
+            )
+        }
+    };
+
+    let delegations = match db::delegations::get_delegations(pool, owner_uuid).await {
+        Ok(delegations) => delegations,
+        Err(err) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": err.to_string()})),
+            )
+        }
+    };
+    let delegated = delegations
+        .iter()
+        .filter_map(|delegation| delegation.prefix.parse::<IpNet>().ok())
+        .any(|delegation| delegation.contains(&prefix));
+    if !delegated {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": "Invalid domain",
+                "message": format!("{prefix} has not been delegated to you")
+            })),
+        );
+    }
+
+    insert_zone(pool, actor, zone_id, owner_uuid).await
+}
+
+async fn insert_zone(
+    pool: &Pool<Postgres>,
+    actor: &Actor,
+    zone_id: &str,
+    owner_uuid: Uuid,
+) -> (StatusCode, Json<Value>) {
+    let soa = Soa::new(zone_id, &NAMESERVERS[0]);
+    let zone = db::zones::create_zone(pool, actor, zone_id, owner_uuid, &soa).await;
     if let Err(err) = zone {
         match err {
             Error::Database(e) if e.code().unwrap_or(std::borrow::Cow::Borrowed("")) == "23505" => {
@@ -143,7 +279,8 @@ pub async fn create_zone(
     let zone = zone.unwrap();
 
     for ns in NAMESERVERS.iter() {
-        if let Err(e) = db::records::create_record(&pool, &zone.id, &zone_id, "NS", ns, 3600).await
+        if let Err(e) =
+            db::records::create_record(pool, actor, &zone.id, &ns_record(&zone.id, ns)).await
         {
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -151,8 +288,152 @@ pub async fn create_zone(
             );
         }
     }
+    db::audit::record(pool, actor, "zone.create", true, json!({ "zone": zone.id }));
+
+    (StatusCode::OK, Json(idn::with_unicode(&zone, "id")))
+}
+
+fn ns_record(zone_id: &str, ns: &str) -> RecordData {
+    RecordData {
+        name: zone_id.to_owned(),
+        record_type: String::from("NS"),
+        content: ns.to_owned(),
+        ttl: 3600,
+        expires_at: None,
+        comment: None,
+        tags: Vec::new(),
+        enabled: true,
+    }
+}
+
+pub async fn update_zone(
+    Path(zone_id): Path<String>,
+    Jwt(user): Jwt,
+    if_match: IfMatch,
+    Json(data): Json<requests::ZoneSettings>,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> Response {
+    let zone = match db::zones::get_zone(&pool, &normalize_zone_id(&zone_id)).await {
+        Ok(zone) => zone,
+        Err(_) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Zone not found"})),
+            )
+                .into_response()
+        }
+    };
+    if let Err(e) = members::authorize(&pool, &zone, user.sub, Role::Owner).await {
+        return e.into_response();
+    }
+    let result = db::zones::update_zone(&pool, &zone.id, |zone| {
+        if_match.check(&zone.etag())?;
+        match data.default_ttl.map(rules::validate_ttl) {
+            Some(Ok(ttl)) => Ok(ttl),
+            Some(Err(e)) => Err((StatusCode::BAD_REQUEST, Json(json!({ "error": e })))),
+            None => Ok(zone.default_ttl),
+        }
+    })
+    .await;
+
+    match result {
+        Ok(Ok(zone)) => (
+            StatusCode::OK,
+            [(header::ETAG, zone.etag())],
+            Json(idn::with_unicode(&zone, "id")),
+        )
+            .into_response(),
+        Ok(Err(e)) => e.into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": err.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn get_soa(
+    Path(zone_id): Path<String>,
+    Jwt(user): Jwt,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> impl IntoResponse {
+    let zone = match db::zones::get_zone(&pool, &normalize_zone_id(&zone_id)).await {
+        Ok(zone) => zone,
+        Err(_) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Zone not found"})),
+            )
+        }
+    };
+    if let Err(e) = members::authorize(&pool, &zone, user.sub, Role::Viewer).await {
+        return e;
+    }
+
+    match db::zones::get_soa(&pool, &zone.id).await {
+        Ok(soa) => (StatusCode::OK, Json(json!(soa))),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": err.to_string()})),
+        ),
+    }
+}
+
+pub async fn update_soa(
+    Path(zone_id): Path<String>,
+    Jwt(user): Jwt,
+    Json(data): Json<requests::Soa>,
+    Extension(pool): Extension<Arc<Pool<Postgres>>>,
+) -> impl IntoResponse {
+    let zone = match db::zones::get_zone(&pool, &normalize_zone_id(&zone_id)).await {
+        Ok(zone) => zone,
+        Err(_) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Zone not found"})),
+            )
+        }
+    };
+    if let Err(e) = members::authorize(&pool, &zone, user.sub, Role::Owner).await {
+        return e;
+    }
+
+    let result = db::zones::update_soa(&pool, &zone.id, |current| -> Result<Soa, String> {
+        let rname = match data.rname {
+            Some(rname) if rname.contains('@') => soa::rname_from_email(&rname)?,
+            Some(rname) => {
+                names::check_hostname(&rname)?;
+                ensure_trailing_dot(&rname)
+            }
+            None => current.rname.clone(),
+        };
+        let soa = Soa {
+            rname,
+            refresh: data.refresh.unwrap_or(current.refresh),
+            retry: data.retry.unwrap_or(current.retry),
+            expire: data.expire.unwrap_or(current.expire),
+            minimum: data.minimum.unwrap_or(current.minimum),
+            ..current.clone()
+        };
+        soa.validate()?;
+        Ok(soa)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(soa)) => (StatusCode::OK, Json(json!(soa))),
+        Ok(Err(e)) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": err.to_string()})),
+        ),
+    }
+}
 
-    (StatusCode::OK, Json(json!(zone)))
+/// Normalizes a zone id taken from a request path, accepting Unicode names
+pub(crate) fn normalize_zone_id(zone_id: &str) -> String {
+    let zone_id = ensure_trailing_dot(zone_id);
+    idn::to_ascii(&zone_id).unwrap_or(zone_id)
 }
 
 pub(crate) fn ensure_trailing_dot(domain: &str) -> String {
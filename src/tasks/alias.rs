@@ -0,0 +1,199 @@
+use crate::db;
+use crate::db::models::Record;
+use axum::async_trait;
+use log::{error, info, warn};
+use sqlx::{Pool, Postgres};
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Looks up the addresses an ALIAS target points to
+#[async_trait]
+pub trait Resolver: Send + Sync {
+    async fn lookup(&self, name: &str) -> Result<Vec<IpAddr>, String>;
+}
+
+/// Resolves names using the system resolver
+pub struct SystemResolver;
+
+#[async_trait]
+impl Resolver for SystemResolver {
+    async fn lookup(&self, name: &str) -> Result<Vec<IpAddr>, String> {
+        let addrs = tokio::net::lookup_host((name.trim_end_matches('.'), 0))
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(addrs
+            .map(|addr| addr.ip())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect())
+    }
+}
+
+/// Periodically resolves every ALIAS record and keeps its synthesized A/AAAA
+/// records up to date
+pub async fn run(pool: Arc<Pool<Postgres>>, resolver: Arc<dyn Resolver>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        if let Err(e) = refresh_all(&pool, resolver.as_ref()).await {
+            error!("Failed to refresh ALIAS records: {e}");
+        }
+    }
+}
+
+pub async fn refresh_all(
+    pool: &Pool<Postgres>,
+    resolver: &dyn Resolver,
+) -> Result<(), sqlx::Error> {
+    for alias in db::records::get_alias_records(pool).await? {
+        let addrs = match resolve(resolver, &alias).await {
+            Some(addrs) => addrs,
+            None => continue,
+        };
+        match db::records::replace_alias_targets(pool, &alias, &addrs).await {
+            Ok(true) => info!(
+                "Updated ALIAS {} -> {}: {addrs:?}",
+                alias.name, alias.content
+            ),
+            Ok(false) => {}
+            Err(e) => error!("Failed to update ALIAS {}: {e}", alias.id),
+        }
+    }
+    Ok(())
+}
+
+/// Resolves an ALIAS's target, or `None` if there's nothing to update it with
+async fn resolve(resolver: &dyn Resolver, alias: &Record) -> Option<Vec<IpAddr>> {
+    match resolver.lookup(&alias.content).await {
+        Ok(addrs) if !addrs.is_empty() => Some(addrs),
+        // Keep serving the last known addresses rather than nothing
+        Ok(_) => {
+            warn!("ALIAS {} -> {} has no addresses", alias.name, alias.content);
+            None
+        }
+        Err(e) => {
+            warn!(
+                "Could not resolve ALIAS {} -> {}: {e}",
+                alias.name, alias.content
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use sqlx::types::Uuid;
+    use std::collections::HashMap;
+
+    /// Answers from a fixed table instead of the network
+    struct StubResolver(HashMap<&'static str, Vec<IpAddr>>);
+
+    #[async_trait]
+    impl Resolver for StubResolver {
+        async fn lookup(&self, name: &str) -> Result<Vec<IpAddr>, String> {
+            self.0
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("{name} not found"))
+        }
+    }
+
+    fn record(record_type: &str, name: &str, content: &str, alias_id: Option<Uuid>) -> Record {
+        Record {
+            id: Uuid::new_v4(),
+            zone_id: String::from("example.com."),
+            name: String::from(name),
+            record_type: String::from(record_type),
+            content: String::from(content),
+            ttl: 300,
+            created_at: Utc::now(),
+            modified_at: Utc::now(),
+            alias_id,
+            expires_at: None,
+            comment: None,
+            tags: vec![],
+            enabled: true,
+        }
+    }
+
+    fn resolver() -> StubResolver {
+        StubResolver(HashMap::from([
+            (
+                "target.example.net.",
+                vec!["192.0.2.1".parse().unwrap(), "2001:db8::1".parse().unwrap()],
+            ),
+            ("empty.example.net.", vec![]),
+        ]))
+    }
+
+    /// What a refresh would write for `alias`, given its current targets
+    async fn refresh(alias: &Record, current: &[Record]) -> Option<Vec<(&'static str, String)>> {
+        let addrs = resolve(&resolver(), alias).await?;
+        db::records::alias_targets(alias, current, &addrs)
+            .map(|wanted| wanted.into_iter().collect())
+    }
+
+    #[tokio::test]
+    async fn refresh_creates_targets() {
+        let alias = record("ALIAS", "example.com.", "target.example.net.", None);
+        assert_eq!(
+            refresh(&alias, &[]).await,
+            Some(vec![
+                ("A", String::from("192.0.2.1")),
+                ("AAAA", String::from("2001:db8::1")),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn refresh_is_a_no_op_when_nothing_changed() {
+        let alias = record("ALIAS", "example.com.", "target.example.net.", None);
+        let current = [
+            record("A", "example.com.", "192.0.2.1", Some(alias.id)),
+            record("AAAA", "example.com.", "2001:db8::1", Some(alias.id)),
+        ];
+        assert_eq!(refresh(&alias, &current).await, None);
+    }
+
+    #[tokio::test]
+    async fn refresh_drops_removed_targets() {
+        let alias = record("ALIAS", "example.com.", "target.example.net.", None);
+        let current = [
+            record("A", "example.com.", "192.0.2.1", Some(alias.id)),
+            record("A", "example.com.", "192.0.2.2", Some(alias.id)),
+            record("AAAA", "example.com.", "2001:db8::1", Some(alias.id)),
+        ];
+        assert_eq!(
+            refresh(&alias, &current).await,
+            Some(vec![
+                ("A", String::from("192.0.2.1")),
+                ("AAAA", String::from("2001:db8::1")),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn refresh_follows_alias_changes() {
+        let mut alias = record("ALIAS", "example.com.", "target.example.net.", None);
+        let current = [
+            record("A", "example.com.", "192.0.2.1", Some(alias.id)),
+            record("AAAA", "example.com.", "2001:db8::1", Some(alias.id)),
+        ];
+        alias.ttl = 60;
+        assert!(refresh(&alias, &current).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn refresh_keeps_targets_when_resolution_fails() {
+        let alias = record("ALIAS", "example.com.", "missing.example.net.", None);
+        let current = [record("A", "example.com.", "192.0.2.1", Some(alias.id))];
+        assert_eq!(refresh(&alias, &current).await, None);
+        let alias = record("ALIAS", "example.com.", "empty.example.net.", None);
+        assert_eq!(refresh(&alias, &current).await, None);
+    }
+}
@@ -0,0 +1,37 @@
+use crate::db;
+use crate::db::models::Actor;
+use log::{error, info};
+use sqlx::{Pool, Postgres};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Periodically deletes records whose `expires_at` has passed
+pub async fn run(pool: Arc<Pool<Postgres>>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        if let Err(e) = sweep(&pool).await {
+            error!("Failed to sweep expired records: {e}");
+        }
+    }
+}
+
+pub async fn sweep(pool: &Pool<Postgres>) -> Result<(), sqlx::Error> {
+    // Deletions are logged to the record history without a user
+    let actor = Actor {
+        user_id: None,
+        client_ip: None,
+    };
+    for record in db::records::get_expired_records(pool).await? {
+        match db::records::delete_expired_record(pool, &actor, &record.zone_id, &record.id).await {
+            Ok(Some(record)) => info!(
+                "Deleted expired record {} {} in {}",
+                record.name, record.record_type, record.zone_id
+            ),
+            // Deleted or given a later expiry since it was listed
+            Ok(None) => {}
+            Err(e) => error!("Failed to delete expired record {}: {e}", record.id),
+        }
+    }
+    Ok(())
+}
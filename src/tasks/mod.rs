@@ -0,0 +1,3 @@
+pub mod alias;
+pub mod expiry;
+pub mod scheduled;
@@ -0,0 +1,100 @@
+use crate::db;
+use crate::db::models::{Actor, Role, ScheduledChange};
+use crate::routes::v1::records;
+use log::{error, info, warn};
+use sqlx::{Acquire, Pool, Postgres, Transaction};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Periodically applies scheduled changes that have come due
+pub async fn run(pool: Arc<Pool<Postgres>>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        if let Err(e) = apply_due(&pool).await {
+            error!("Failed to apply scheduled changes: {e}");
+        }
+    }
+}
+
+/// Applies every change that has come due. Each change is claimed, applied and
+/// marked as finished in one transaction, so a crash part way through leaves it
+/// pending to be picked up again, and other instances skip it in the meantime.
+/// A change that can't be applied is marked as failed, but one that hits a
+/// database error is left pending and retried on the next run.
+pub async fn apply_due(pool: &Pool<Postgres>) -> Result<(), sqlx::Error> {
+    loop {
+        let mut transaction = pool.begin().await?;
+        let scheduled = match db::scheduled::claim_due_scheduled_change(&mut transaction).await? {
+            Some(scheduled) => scheduled,
+            None => return Ok(()),
+        };
+        // The records are written under a savepoint, so a failed change can
+        // still be marked as failed without keeping any of its writes
+        let mut savepoint = transaction.begin().await?;
+        match apply(pool, &mut savepoint, &scheduled).await {
+            Ok(Ok(())) => {
+                savepoint.commit().await?;
+                info!(
+                    "Applied scheduled change {} to {}",
+                    scheduled.id, scheduled.zone_id
+                );
+                db::scheduled::finish_scheduled_change(&mut transaction, &scheduled.id, None)
+                    .await?;
+            }
+            Ok(Err(e)) => {
+                savepoint.rollback().await?;
+                warn!(
+                    "Scheduled change {} to {} failed: {e}",
+                    scheduled.id, scheduled.zone_id
+                );
+                db::scheduled::finish_scheduled_change(&mut transaction, &scheduled.id, Some(&e))
+                    .await?;
+            }
+            Err(e) => {
+                // Give up the claim so the change is retried on the next run,
+                // and leave the rest of the due changes until then too
+                savepoint.rollback().await?;
+                transaction.rollback().await?;
+                warn!(
+                    "Scheduled change {} to {} will be retried: {e}",
+                    scheduled.id, scheduled.zone_id
+                );
+                return Ok(());
+            }
+        }
+        transaction.commit().await?;
+    }
+}
+
+/// Checks the changes against the zone as it is now and applies them, on behalf
+/// of the user who scheduled them. The inner error says why the change can't be
+/// applied; the outer one is a database error worth retrying.
+async fn apply(
+    pool: &Pool<Postgres>,
+    transaction: &mut Transaction<'_, Postgres>,
+    scheduled: &ScheduledChange,
+) -> Result<Result<(), String>, sqlx::Error> {
+    let role = db::members::get_role(pool, &scheduled.zone_id, scheduled.created_by).await?;
+    if role < Some(Role::Editor) {
+        return Ok(Err(String::from(
+            "The user who scheduled this change can no longer edit the zone",
+        )));
+    }
+    let changes = match serde_json::from_value(scheduled.changes.clone()) {
+        Ok(changes) => changes,
+        Err(e) => return Ok(Err(e.to_string())),
+    };
+
+    let actor = Actor {
+        user_id: Some(scheduled.created_by),
+        client_ip: None,
+    };
+    let written =
+        db::records::write_zone_in(transaction, &actor, &scheduled.zone_id, |zone, records| {
+            records::plan_changes(zone, records, changes)
+                .map_err(|(index, _, e)| format!("Change {index}: {e}"))
+        })
+        .await?;
+    Ok(written.map(|_| ()))
+}